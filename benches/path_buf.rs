@@ -0,0 +1,103 @@
+//! Reading a page of result paths: a fresh 32768-unit buffer per search (what search did before the
+//! thread-local buffer) against src/pathbuf.rs reading into one reused buffer. Allocations per page are
+//! counted and printed before the timings.
+//!
+//! cargo bench --bench path_buf
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::RefCell;
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+#[path = "../src/pathbuf.rs"]
+mod pathbuf;
+
+struct Counting;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, l: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(l.size(), Ordering::Relaxed);
+        System.alloc(l)
+    }
+
+    unsafe fn alloc_zeroed(&self, l: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(l.size(), Ordering::Relaxed);
+        System.alloc_zeroed(l)
+    }
+
+    unsafe fn dealloc(&self, p: *mut u8, l: Layout) { System.dealloc(p, l) }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const MAX_PATH_BUF: usize = 32768;
+
+thread_local! {
+    static PATH_BUF: RefCell<Vec<u16>> = RefCell::new(vec![0u16; 1024]);
+}
+
+/// Everything_GetResultFullPathNameW over a fixed page
+fn full_path(page: &[Vec<u16>], i: usize, buf: &mut [u16]) -> u32 {
+    let p = &page[i];
+    if buf.is_empty() { return p.len() as u32; }
+    let n = p.len().min(buf.len() - 1);
+    buf[..n].copy_from_slice(&p[..n]);
+    buf[n] = 0;
+    n as u32
+}
+
+fn fresh(page: &[Vec<u16>]) -> Vec<String> {
+    let mut buf = vec![0u16; MAX_PATH_BUF];
+    (0..page.len()).map(|i| {
+        full_path(page, i, &mut buf);
+        let end = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+        String::from_utf16_lossy(&buf[..end])
+    }).collect()
+}
+
+fn reused(page: &[Vec<u16>]) -> Vec<String> {
+    PATH_BUF.with(|b| {
+        let mut buf = b.borrow_mut();
+        (0..page.len()).map(|i| {
+            let (n, _) = pathbuf::read_into(&mut buf, |s| full_path(page, i, s));
+            String::from_utf16_lossy(&buf[..n])
+        }).collect()
+    })
+}
+
+/// `n` paths of typical length (60-110 units)
+fn page(n: usize) -> Vec<Vec<u16>> {
+    (0..n).map(|i| format!(r"C:\Users\me\Projects\app{}\src\{}\module_{}.rs", i % 7, "sub\\".repeat(i % 12), i).encode_utf16().collect()).collect()
+}
+
+/// Allocations and bytes requested by one call of `f`, after a warm-up call
+fn allocations(f: impl Fn() -> Vec<String>) -> (usize, usize) {
+    drop(f());
+    let (a, b) = (ALLOCS.load(Ordering::Relaxed), BYTES.load(Ordering::Relaxed));
+    drop(black_box(f()));
+    (ALLOCS.load(Ordering::Relaxed) - a, BYTES.load(Ordering::Relaxed) - b)
+}
+
+fn bench(c: &mut Criterion) {
+    let mut g = c.benchmark_group("path_page");
+    for n in [1, 50, 500] {
+        let p = page(n);
+        let (fa, fb) = allocations(|| fresh(&p));
+        let (ra, rb) = allocations(|| reused(&p));
+        println!("{:>3} paths: fresh buffer {} allocations / {} bytes, reused buffer {} allocations / {} bytes", n, fa, fb, ra, rb);
+        g.bench_with_input(BenchmarkId::new("fresh_32k", n), &p, |b, p| b.iter(|| fresh(black_box(p))));
+        g.bench_with_input(BenchmarkId::new("thread_local", n), &p, |b, p| b.iter(|| reused(black_box(p))));
+    }
+    g.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -0,0 +1,191 @@
+//! The Everything SDK calls a search makes, as a trait: EvDll forwards them to Everything64.dll, and in tests
+//! MockApi answers from a fixed result list so the query setup and result reading can run without Windows
+
+use widestring::U16CStr;
+
+use crate::{run_query, EvDll, SearchError};
+
+/// Everything_* by another name; indexes are into the current result page
+pub trait EverythingApi {
+    fn set_search(&self, q: &U16CStr);
+    fn set_max(&self, max: u32);
+    fn set_match_case(&self, on: bool);
+    fn set_match_whole_word(&self, on: bool);
+    fn set_regex(&self, on: bool);
+    fn set_match_path(&self, on: bool);
+    fn set_request_flags(&self, flags: u32);
+    fn set_sort(&self, sort: u32);
+    fn set_offset(&self, offset: u32);
+    /// Execute the configured search, with a deadline when `timeout_ms` is set (Windows only)
+    fn query(&self, timeout_ms: u32) -> Result<(), SearchError>;
+    fn num_results(&self) -> u32;
+    fn total_results(&self) -> u32;
+    /// Everything_GetResultFullPathNameW: copies at most `buf.len() - 1` units plus a nul and returns the length
+    /// copied; an empty `buf` returns the length the full path needs
+    fn full_path(&self, i: u32, buf: &mut [u16]) -> u32;
+    /// The string getters are None where the DLL returns null
+    fn file_name(&self, i: u32) -> Option<String>;
+    fn extension(&self, i: u32) -> Option<String>;
+    fn highlighted_file_name(&self, i: u32) -> Option<String>;
+    fn file_list_file_name(&self, i: u32) -> Option<String>;
+    fn size(&self, i: u32) -> Option<i64>;
+    /// FILETIMEs
+    fn date_modified(&self, i: u32) -> Option<u64>;
+    fn date_created(&self, i: u32) -> Option<u64>;
+    fn date_accessed(&self, i: u32) -> Option<u64>;
+    fn attributes(&self, i: u32) -> u32;
+    fn run_count(&self, i: u32) -> u32;
+}
+
+/// Null-terminated UTF-16 from the DLL, owned by its result list
+unsafe fn wide(p: *const u16) -> Option<String> {
+    (!p.is_null()).then(|| U16CStr::from_ptr_str(p).to_string_lossy())
+}
+
+// The DLL has no thread affinity but one global state; every call here comes from the DLL thread (see worker.rs)
+impl EverythingApi for EvDll {
+    fn set_search(&self, q: &U16CStr) { unsafe { (self.set_search)(q.as_ptr()) } }
+    fn set_max(&self, max: u32) { unsafe { (self.set_max)(max) } }
+    fn set_match_case(&self, on: bool) { unsafe { (self.set_case)(on as i32) } }
+    fn set_match_whole_word(&self, on: bool) { unsafe { (self.set_word)(on as i32) } }
+    fn set_regex(&self, on: bool) { unsafe { (self.set_regex)(on as i32) } }
+    fn set_match_path(&self, on: bool) { unsafe { (self.set_path)(on as i32) } }
+    fn set_request_flags(&self, flags: u32) { unsafe { (self.set_flags)(flags) } }
+    fn set_sort(&self, sort: u32) { unsafe { (self.set_sort)(sort) } }
+    fn set_offset(&self, offset: u32) { unsafe { (self.set_offset)(offset) } }
+    fn query(&self, timeout_ms: u32) -> Result<(), SearchError> { unsafe { run_query(self, timeout_ms) } }
+    fn num_results(&self) -> u32 { unsafe { (self.get_num)() } }
+    fn total_results(&self) -> u32 { unsafe { (self.get_tot)() } }
+
+    fn full_path(&self, i: u32, buf: &mut [u16]) -> u32 {
+        let ptr = if buf.is_empty() { std::ptr::null_mut() } else { buf.as_mut_ptr() };
+        unsafe { (self.get_path)(i, ptr, buf.len() as u32) }
+    }
+
+    fn file_name(&self, i: u32) -> Option<String> { unsafe { wide((self.get_file_name)(i)) } }
+    fn extension(&self, i: u32) -> Option<String> { unsafe { wide((self.get_ext)(i)) } }
+    fn highlighted_file_name(&self, i: u32) -> Option<String> { unsafe { wide((self.get_highlighted)(i)) } }
+    fn file_list_file_name(&self, i: u32) -> Option<String> { unsafe { wide((self.get_file_list)(i)) } }
+
+    fn size(&self, i: u32) -> Option<i64> {
+        let mut n = 0i64;
+        (unsafe { (self.get_size)(i, &mut n) } != 0).then_some(n)
+    }
+
+    fn date_modified(&self, i: u32) -> Option<u64> { date(&self.get_date_modified, i) }
+    fn date_created(&self, i: u32) -> Option<u64> { date(&self.get_date_created, i) }
+    fn date_accessed(&self, i: u32) -> Option<u64> { date(&self.get_date_accessed, i) }
+    fn attributes(&self, i: u32) -> u32 { unsafe { (self.get_attr)(i) } }
+    fn run_count(&self, i: u32) -> u32 { unsafe { (self.get_run_count)(i) } }
+}
+
+fn date(f: &crate::GetDateFn, i: u32) -> Option<u64> {
+    let mut ft = 0u64;
+    (unsafe { f(i, &mut ft) } != 0).then_some(ft)
+}
+
+/// One result as MockApi serves it; unset fields read as the DLL's "not requested" answers
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MockResult {
+    pub path: String,
+    pub file_name: Option<String>,
+    pub extension: Option<String>,
+    pub highlighted: Option<String>,
+    pub file_list: Option<String>,
+    pub size: Option<i64>,
+    pub modified: Option<u64>,
+    pub created: Option<u64>,
+    pub accessed: Option<u64>,
+    pub attributes: u32,
+    pub run_count: u32,
+}
+
+/// The settings the last search was configured with, as the DLL would hold them
+#[cfg(test)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MockState {
+    pub search: String,
+    pub max: u32,
+    pub case: bool,
+    pub word: bool,
+    pub regex: bool,
+    pub path: bool,
+    pub flags: u32,
+    pub sort: u32,
+    pub offset: u32,
+    pub timeout_ms: u32,
+}
+
+/// Serves `results[offset..offset + max]` of a fixed list with `total` as the total, and records the settings
+#[cfg(test)]
+#[derive(Default)]
+pub struct MockApi {
+    pub results: Vec<MockResult>,
+    /// results.len() when None
+    pub total: Option<u32>,
+    /// Returned by query() instead of running it
+    pub fail: Option<crate::EverythingError>,
+    pub state: std::cell::RefCell<MockState>,
+    /// Lengths full_path was called with, to see the path buffer grow
+    pub path_calls: std::cell::RefCell<Vec<usize>>,
+}
+
+#[cfg(test)]
+impl MockApi {
+    pub fn new(paths: &[&str]) -> Self {
+        Self { results: paths.iter().map(|p| MockResult { path: p.to_string(), ..Default::default() }).collect(), ..Default::default() }
+    }
+
+    fn page(&self) -> &[MockResult] {
+        let s = self.state.borrow();
+        let start = (s.offset as usize).min(self.results.len());
+        &self.results[start..(start + s.max as usize).min(self.results.len())]
+    }
+
+    fn get(&self, i: u32) -> Option<&MockResult> { self.page().get(i as usize) }
+}
+
+#[cfg(test)]
+impl EverythingApi for MockApi {
+    fn set_search(&self, q: &U16CStr) { self.state.borrow_mut().search = q.to_string_lossy(); }
+    fn set_max(&self, max: u32) { self.state.borrow_mut().max = max; }
+    fn set_match_case(&self, on: bool) { self.state.borrow_mut().case = on; }
+    fn set_match_whole_word(&self, on: bool) { self.state.borrow_mut().word = on; }
+    fn set_regex(&self, on: bool) { self.state.borrow_mut().regex = on; }
+    fn set_match_path(&self, on: bool) { self.state.borrow_mut().path = on; }
+    fn set_request_flags(&self, flags: u32) { self.state.borrow_mut().flags = flags; }
+    fn set_sort(&self, sort: u32) { self.state.borrow_mut().sort = sort; }
+    fn set_offset(&self, offset: u32) { self.state.borrow_mut().offset = offset; }
+
+    fn query(&self, timeout_ms: u32) -> Result<(), SearchError> {
+        self.state.borrow_mut().timeout_ms = timeout_ms;
+        self.fail.map_or(Ok(()), |e| Err(SearchError::QueryFailed(e)))
+    }
+
+    fn num_results(&self) -> u32 { self.page().len() as u32 }
+    fn total_results(&self) -> u32 { self.total.unwrap_or(self.results.len() as u32) }
+
+    fn full_path(&self, i: u32, buf: &mut [u16]) -> u32 {
+        self.path_calls.borrow_mut().push(buf.len());
+        let Some(r) = self.get(i) else { return 0 };
+        let w = widestring::U16CString::from_str(&r.path).unwrap();
+        let w = w.as_slice();
+        if buf.is_empty() { return w.len() as u32; }
+        let n = w.len().min(buf.len() - 1);
+        buf[..n].copy_from_slice(&w[..n]);
+        buf[n] = 0;
+        n as u32
+    }
+
+    fn file_name(&self, i: u32) -> Option<String> { self.get(i)?.file_name.clone() }
+    fn extension(&self, i: u32) -> Option<String> { self.get(i)?.extension.clone() }
+    fn highlighted_file_name(&self, i: u32) -> Option<String> { self.get(i)?.highlighted.clone() }
+    fn file_list_file_name(&self, i: u32) -> Option<String> { self.get(i)?.file_list.clone() }
+    fn size(&self, i: u32) -> Option<i64> { self.get(i)?.size }
+    fn date_modified(&self, i: u32) -> Option<u64> { self.get(i)?.modified }
+    fn date_created(&self, i: u32) -> Option<u64> { self.get(i)?.created }
+    fn date_accessed(&self, i: u32) -> Option<u64> { self.get(i)?.accessed }
+    fn attributes(&self, i: u32) -> u32 { self.get(i).map_or(0, |r| r.attributes) }
+    fn run_count(&self, i: u32) -> u32 { self.get(i).map_or(0, |r| r.run_count) }
+}
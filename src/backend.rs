@@ -0,0 +1,237 @@
+//! Search backends: Everything on Windows, Spotlight (mdfind) or locate elsewhere, and a canned-list mock
+//!
+//! The fallbacks understand a subset of Everything syntax: plain terms and phrases (all must match the
+//! name, or the path with match_path), `*`/`?` wildcards, `ext:`, `file:`, `folder:`, `nopath:` and `wfn:`.
+//! OR, NOT, groups and other functions are rejected. Sorting is by name or path only, and timeout_ms is ignored.
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+use chrono::{DateTime, Utc};
+use regex::{Regex, RegexBuilder};
+
+use crate::explain::{tokenize, Tok};
+use crate::{rfc3339, Hit, Opts, SearchError};
+
+#[async_trait::async_trait]
+pub trait FileSearcher: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// Total matches and the requested page of hits
+    async fn search(&self, q: &str, o: &Opts) -> Result<(u32, Vec<Hit>), SearchError>;
+    /// Total matches only; the default runs a one-result search
+    async fn count(&self, q: &str, o: &Opts) -> Result<u32, SearchError> {
+        self.search(q, &Opts { max: 1, offset: 0, ..*o }).await.map(|(total, _)| total)
+    }
+    /// One-line readiness check for the status tool
+    fn status(&self) -> Result<String, SearchError> { Ok("Ready".into()) }
+}
+
+/// macOS Spotlight via `mdfind -name`
+pub struct Spotlight;
+
+#[async_trait::async_trait]
+impl FileSearcher for Spotlight {
+    fn name(&self) -> &'static str { "Spotlight (mdfind)" }
+
+    fn status(&self) -> Result<String, SearchError> {
+        run("mdfind", &["-count", "-onlyin", "/", "-name", "."]).map(|_| "Ready (reduced query syntax)".into())
+    }
+
+    async fn search(&self, q: &str, o: &Opts) -> Result<(u32, Vec<Hit>), SearchError> {
+        if o.regex { return Err(SearchError::BadQuery("regex is not supported by the Spotlight backend".into())); }
+        let f = Filter::parse(q, o, self.name())?;
+        let paths = run_async("mdfind", &["-name", &f.literal()?]).await?;
+        Ok(f.page(paths, o))
+    }
+}
+
+/// Linux/BSD `locate` (mlocate, plocate or findutils)
+pub struct Locate;
+
+#[async_trait::async_trait]
+impl FileSearcher for Locate {
+    fn name(&self) -> &'static str { "locate" }
+
+    fn status(&self) -> Result<String, SearchError> {
+        let v = run("locate", &["--version"])?;
+        Ok(format!("{} - Ready (reduced query syntax)", v.first().map(|l| l.trim()).unwrap_or("locate")))
+    }
+
+    async fn search(&self, q: &str, o: &Opts) -> Result<(u32, Vec<Hit>), SearchError> {
+        let (f, pattern, mut args) = if o.regex {
+            (Filter::regex(q, o)?, q.to_string(), vec!["--regex"])
+        } else {
+            let f = Filter::parse(q, o, self.name())?;
+            let lit = f.literal()?;
+            (f, lit, Vec::new())
+        };
+        if !o.case { args.push("-i"); }
+        args.extend(["--", &pattern]);
+        Ok(f.page(run_async("locate", &args).await?, o))
+    }
+}
+
+/// Canned results: one path per line from the named file, filtered, sorted and paged like the other fallbacks.
+/// Lets query composition, formatting and paging run without Everything, e.g. in CI
+pub struct Mock(pub String);
+
+#[async_trait::async_trait]
+impl FileSearcher for Mock {
+    fn name(&self) -> &'static str { "mock" }
+
+    fn status(&self) -> Result<String, SearchError> {
+        std::fs::metadata(&self.0).map(|_| format!("Ready (canned results from {})", self.0))
+            .map_err(|e| SearchError::Backend(format!("{}: {}", self.0, e)))
+    }
+
+    async fn search(&self, q: &str, o: &Opts) -> Result<(u32, Vec<Hit>), SearchError> {
+        let f = if o.regex { Filter::regex(q, o)? } else { Filter::parse(q, o, self.name())? };
+        let text = std::fs::read_to_string(&self.0).map_err(|e| SearchError::Backend(format!("{}: {}", self.0, e)))?;
+        let paths = text.lines().map(str::trim).filter(|l| !l.is_empty()).map(String::from).collect();
+        Ok(f.page(paths, o))
+    }
+}
+
+/// Run a command from the sync status check and return its output lines
+fn run(cmd: &str, args: &[&str]) -> Result<Vec<String>, SearchError> {
+    lines(cmd, Command::new(cmd).args(args).output())
+}
+
+/// Run a search command without holding up the runtime and return its output lines
+async fn run_async(cmd: &str, args: &[&str]) -> Result<Vec<String>, SearchError> {
+    lines(cmd, tokio::process::Command::new(cmd).args(args).output().await)
+}
+
+/// Output lines of a finished command; exit code 1 with no stderr means "no matches"
+fn lines(cmd: &str, out: std::io::Result<Output>) -> Result<Vec<String>, SearchError> {
+    let out = out.map_err(|e| SearchError::Backend(format!("{}: {}", cmd, e)))?;
+    let err = String::from_utf8_lossy(&out.stderr);
+    if !out.status.success() && !err.trim().is_empty() {
+        return Err(SearchError::Backend(format!("{}: {}", cmd, err.trim())));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).lines().filter(|l| !l.is_empty()).map(String::from).collect())
+}
+
+/// Client-side matching for what the command line tools can't express
+struct Filter {
+    /// Every pattern must match the name (or full path with match_path)
+    pats: Vec<Regex>,
+    /// Literal runs of the terms, used to pick the pre-filter passed to the tool
+    lits: Vec<String>,
+    exts: Vec<String>,
+    /// Some(true) = files only, Some(false) = folders only
+    files: Option<bool>,
+}
+
+impl Filter {
+    fn parse(q: &str, o: &Opts, backend: &str) -> Result<Self, SearchError> {
+        let (toks, errs) = tokenize(q);
+        if let Some(e) = errs.into_iter().next() { return Err(SearchError::BadQuery(e)); }
+        let mut f = Filter { pats: Vec::new(), lits: Vec::new(), exts: Vec::new(), files: None };
+        for t in toks {
+            match t {
+                Tok::Term(w) | Tok::Phrase(w) => f.term(&w, o)?,
+                Tok::Func(n, v) if n == "ext" => f.exts.extend(v.split(';').filter(|e| !e.is_empty()).map(|e| e.trim_start_matches('.').to_lowercase())),
+                Tok::Func(n, v) if n == "wfn" || n == "wholefilename" => f.exact(&v, o)?,
+                // Terms already match the name unless match_path is set
+                Tok::Func(n, v) if n == "nopath" && !o.path => f.term(&v, o)?,
+                Tok::Func(n, _) if n == "file" || n == "files" => f.files = Some(true),
+                Tok::Func(n, _) if n == "folder" || n == "folders" => f.files = Some(false),
+                Tok::Func(n, _) => return Err(SearchError::BadQuery(format!("'{}:' is not supported by the {} backend", n, backend))),
+                _ => return Err(SearchError::BadQuery(format!("OR, NOT and <> groups are not supported by the {} backend", backend))),
+            }
+        }
+        Ok(f)
+    }
+
+    fn regex(q: &str, o: &Opts) -> Result<Self, SearchError> {
+        let re = RegexBuilder::new(q).case_insensitive(!o.case).build().map_err(|e| SearchError::BadQuery(e.to_string()))?;
+        Ok(Filter { pats: vec![re], lits: Vec::new(), exts: Vec::new(), files: None })
+    }
+
+    /// Wildcard terms match the whole name like Everything; plain terms are substrings
+    fn term(&mut self, w: &str, o: &Opts) -> Result<(), SearchError> {
+        let pat = if w.contains(['*', '?']) {
+            let body: String = w.chars().map(|c| match c {
+                '*' => ".*".to_string(),
+                '?' => ".".to_string(),
+                c => regex::escape(&c.to_string()),
+            }).collect();
+            format!("^{}$", body)
+        } else if o.word {
+            format!(r"\b{}\b", regex::escape(w))
+        } else {
+            regex::escape(w)
+        };
+        self.pats.push(RegexBuilder::new(&pat).case_insensitive(!o.case).build().map_err(|e| SearchError::BadQuery(e.to_string()))?);
+        self.lits.extend(w.split(['*', '?']).filter(|s| !s.is_empty()).map(String::from));
+        Ok(())
+    }
+
+    fn exact(&mut self, name: &str, o: &Opts) -> Result<(), SearchError> {
+        let re = RegexBuilder::new(&format!("^{}$", regex::escape(name))).case_insensitive(!o.case).build();
+        self.pats.push(re.map_err(|e| SearchError::BadQuery(e.to_string()))?);
+        self.lits.push(name.to_string());
+        Ok(())
+    }
+
+    /// Longest literal to hand to the tool so it doesn't list the whole disk
+    fn literal(&self) -> Result<String, SearchError> {
+        self.lits.iter().max_by_key(|l| l.len()).cloned()
+            .or_else(|| self.exts.first().map(|e| format!(".{}", e)))
+            .ok_or_else(|| SearchError::BadQuery("this backend needs at least one search term or ext:".into()))
+    }
+
+    fn matches(&self, p: &str, o: &Opts) -> bool {
+        let name = Path::new(p).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| p.to_string());
+        let subject = if o.path { p } else { &name };
+        if !self.pats.iter().all(|r| r.is_match(subject)) { return false; }
+        if !self.exts.is_empty() {
+            let ext = Path::new(&name).extension().map(|e| e.to_string_lossy().to_lowercase());
+            if !ext.is_some_and(|e| self.exts.contains(&e)) { return false; }
+        }
+        self.files.is_none_or(|files| Path::new(p).is_dir() != files)
+    }
+
+    fn page(&self, paths: Vec<String>, o: &Opts) -> (u32, Vec<Hit>) {
+        let mut paths: Vec<String> = paths.into_iter().filter(|p| self.matches(p, o)).collect();
+        let name = |p: &String| Path::new(p).file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+        match o.sort.max(1) {
+            1 => paths.sort_by_key(name),
+            2 => { paths.sort_by_key(name); paths.reverse(); }
+            3 => paths.sort_by_key(|p| p.to_lowercase()),
+            4 => { paths.sort_by_key(|p| p.to_lowercase()); paths.reverse(); }
+            _ => {}
+        }
+        let total = paths.len() as u32;
+        let hits = paths.into_iter().enumerate().skip(o.offset as usize).take(o.max.clamp(1, 500) as usize)
+            .map(|(i, path)| hit(i as u32, path, o)).collect();
+        (total, hits)
+    }
+}
+
+fn hit(index: u32, path: String, o: &Opts) -> Hit {
+    let meta = std::fs::metadata(&path).ok();
+    let is_dir = meta.as_ref().is_some_and(|m| m.is_dir());
+    let name = Path::new(&path).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| path.clone());
+    // FILE_ATTRIBUTE_* approximation: readonly, hidden for dotfiles, directory
+    let readonly = meta.as_ref().is_some_and(|m| m.permissions().readonly());
+    let attributes = readonly as u32 | (name.starts_with('.') as u32) << 1 | (is_dir as u32) << 4;
+    Hit {
+        index,
+        extension: Path::new(&path).extension().filter(|_| !is_dir).map(|e| e.to_string_lossy().into_owned()),
+        name,
+        is_dir,
+        size: meta.as_ref().filter(|_| !is_dir).map(|m| m.len()),
+        date_created: meta.as_ref().filter(|_| o.created).and_then(|m| m.created().ok()).map(|t| rfc3339(&DateTime::<Utc>::from(t))),
+        date_accessed: meta.as_ref().filter(|_| o.accessed).and_then(|m| m.accessed().ok()).map(|t| rfc3339(&DateTime::<Utc>::from(t))),
+        date_modified: meta.and_then(|m| m.modified().ok()).map(|t| rfc3339(&DateTime::<Utc>::from(t))),
+        run_count: 0,
+        attributes,
+        highlighted: None,
+        matches: None,
+        file_list: None,
+        live: None,
+        path,
+    }
+}
@@ -0,0 +1,72 @@
+//! Small LRU cache with per-entry expiry for repeated identical searches
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+pub struct TtlCache<K, V> {
+    cap: usize,
+    map: HashMap<K, (Instant, V)>,
+    /// Least recently used first
+    order: VecDeque<K>,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> TtlCache<K, V> {
+    pub fn new(cap: usize) -> Self { Self { cap, map: HashMap::new(), order: VecDeque::new() } }
+
+    /// Value stored within the last `ttl`, refreshing its LRU position
+    pub fn get(&mut self, k: &K, ttl: Duration) -> Option<V> {
+        let fresh = self.map.get(k).is_some_and(|(at, _)| at.elapsed() < ttl);
+        if !fresh {
+            if self.map.remove(k).is_some() { self.order.retain(|x| x != k); }
+            return None;
+        }
+        self.order.retain(|x| x != k);
+        self.order.push_back(k.clone());
+        self.map.get(k).map(|(_, v)| v.clone())
+    }
+
+    pub fn put(&mut self, k: K, v: V) {
+        if self.map.insert(k.clone(), (Instant::now(), v)).is_some() { self.order.retain(|x| x != &k); }
+        self.order.push_back(k);
+        while self.order.len() > self.cap {
+            if let Some(old) = self.order.pop_front() { self.map.remove(&old); }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn expired_entries_are_dropped() {
+        let mut c = TtlCache::new(4);
+        c.put("a", 1);
+        assert_eq!(c.get(&"a", MINUTE), Some(1));
+        assert_eq!(c.get(&"a", Duration::ZERO), None);
+        assert_eq!(c.get(&"a", MINUTE), None);
+    }
+
+    #[test]
+    fn least_recently_used_goes_first() {
+        let mut c = TtlCache::new(2);
+        c.put("a", 1);
+        c.put("b", 2);
+        c.get(&"a", MINUTE);
+        c.put("c", 3);
+        assert_eq!((c.get(&"a", MINUTE), c.get(&"b", MINUTE), c.get(&"c", MINUTE)), (Some(1), None, Some(3)));
+    }
+
+    #[test]
+    fn putting_again_restarts_the_clock() {
+        let mut c = TtlCache::new(2);
+        c.put("id", 1);
+        std::thread::sleep(Duration::from_millis(30));
+        c.put("id", 1);
+        assert_eq!(c.get(&"id", Duration::from_millis(25)), Some(1));
+        assert_eq!(c.order.len(), 1);
+    }
+}
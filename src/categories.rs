@@ -0,0 +1,43 @@
+//! Extension lists behind the category tools (everything_search_audio, ...), with optional overrides
+//! from a TOML or JSON file mapping category names to extension arrays:
+//!
+//! ```toml
+//! audio = ["mp3", "flac", "opus"]
+//! ebook = ["epub", "mobi"]
+//! ```
+
+use std::collections::HashMap;
+
+const DEFAULTS: &[(&str, &[&str])] = &[
+    ("audio", &["mp3", "wav", "flac", "aac", "ogg", "wma", "m4a"]),
+    ("video", &["mp4", "avi", "mkv", "mov", "wmv", "flv", "webm"]),
+    ("image", &["jpg", "jpeg", "png", "gif", "bmp", "svg", "webp", "ico"]),
+    ("doc", &["pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "txt", "md"]),
+    ("code", &["cs", "py", "js", "ts", "java", "cpp", "c", "h", "go", "rs", "rb", "php", "ps1"]),
+    ("archive", &["zip", "rar", "7z", "tar", "gz", "bz2", "iso"]),
+    ("exe", &["exe", "msi", "bat", "cmd", "ps1", "sh"]),
+];
+
+/// Defaults with the file at `path` merged over them; a listed category replaces the default list.
+/// A missing file is not an error, a malformed one is.
+pub fn load(path: Option<&str>) -> Result<HashMap<String, Vec<String>>, String> {
+    let mut map: HashMap<String, Vec<String>> = DEFAULTS.iter()
+        .map(|(n, e)| (n.to_string(), e.iter().map(|e| e.to_string()).collect())).collect();
+    let Some(path) = path.filter(|p| !p.is_empty()) else { return Ok(map) };
+    let text = match std::fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(map),
+        Err(e) => return Err(format!("{}: {}", path, e)),
+    };
+    let overrides: HashMap<String, Vec<String>> = if path.to_lowercase().ends_with(".json") {
+        serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?
+    } else {
+        toml::from_str(&text).map_err(|e| format!("{}: {}", path, e))?
+    };
+    for (name, exts) in overrides {
+        let exts: Vec<String> = exts.iter().map(|e| e.trim().trim_start_matches('.').to_lowercase()).filter(|e| !e.is_empty()).collect();
+        if exts.is_empty() { return Err(format!("{}: category '{}' has no extensions", path, name)); }
+        map.insert(name.to_lowercase(), exts);
+    }
+    Ok(map)
+}
@@ -0,0 +1,114 @@
+//! Zip archive writer for everything_collect
+
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+/// Deepest folder containing every path
+fn common_base(paths: &[String]) -> PathBuf {
+    let mut base: Option<PathBuf> = None;
+    for p in paths {
+        let parent = Path::new(p).parent().unwrap_or(Path::new(""));
+        base = Some(match base {
+            None => parent.to_path_buf(),
+            Some(b) => b.components().zip(parent.components()).take_while(|(a, b)| a == b).map(|(a, _)| a).collect(),
+        });
+    }
+    base.unwrap_or_default()
+}
+
+/// Zip `files` (path, size-or-stat-error) into `target`, naming entries relative to `base` (or their common folder).
+/// An existing `target` is an error unless `overwrite`, and is never added to itself.
+/// Stops adding once `max_files` or `max_bytes` would be exceeded; returns a report of what went in.
+pub fn write_zip(target: &str, overwrite: bool, base: Option<&str>, files: &[(String, io::Result<u64>)], max_files: usize, max_bytes: u64) -> Result<String, String> {
+    let base = base.map(PathBuf::from).unwrap_or_else(|| common_base(&files.iter().map(|(f, _)| f.clone()).collect::<Vec<_>>()));
+    let file = if overwrite { File::create(target) } else { File::options().write(true).create_new(true).open(target) };
+    let file = file.map_err(|e| match e.kind() {
+        io::ErrorKind::AlreadyExists => format!("{} already exists (pass overwrite: true to replace it)", target),
+        _ => format!("Create {}: {}", target, e),
+    })?;
+    // Compared resolved, so another spelling of the target is caught too
+    let own = std::fs::canonicalize(target).ok();
+    let mut zw = ZipWriter::new(file);
+    let opts = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let (mut added, mut skipped, mut bytes) = (Vec::new(), Vec::new(), 0u64);
+
+    for (f, len) in files {
+        let Ok(rel) = Path::new(f).strip_prefix(&base) else { skipped.push(format!("{} (outside base)", f)); continue };
+        if own.is_some() && std::fs::canonicalize(f).ok() == own { skipped.push(format!("{} (the archive itself)", f)); continue; }
+        let len = match len { Ok(n) => *n, Err(e) => { skipped.push(format!("{} ({})", f, e)); continue } };
+        if added.len() >= max_files { skipped.push(format!("{} (file cap {})", f, max_files)); continue; }
+        if bytes.saturating_add(len) > max_bytes { skipped.push(format!("{} (size cap {} bytes)", f, max_bytes)); continue; }
+        let name = rel.to_string_lossy().replace('\\', "/");
+        let copied = File::open(f).and_then(|mut src| {
+            zw.start_file(name.as_str(), opts).map_err(io::Error::other)?;
+            io::copy(&mut src, &mut zw)
+        });
+        match copied {
+            Ok(n) => { bytes += n; added.push(format!("{} ({} bytes)", name, n)); }
+            Err(e) => skipped.push(format!("{} ({})", f, e)),
+        }
+    }
+    zw.finish().map_err(|e| format!("Finish {}: {}", target, e))?;
+
+    let mut out = format!("Wrote {} ({} files, {} bytes, base {})\n\nIncluded:\n", target, added.len(), bytes, base.display());
+    for a in &added { out.push_str(&format!("  {}\n", a)); }
+    if !skipped.is_empty() {
+        out.push_str(&format!("\nSkipped {}:\n", skipped.len()));
+        for s in &skipped { out.push_str(&format!("  {}\n", s)); }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh scratch folder with `a.txt` and `b.txt` in it
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("collect-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for f in ["a.txt", "b.txt"] { std::fs::write(dir.join(f), f).unwrap(); }
+        dir
+    }
+
+    fn inputs(dir: &Path, names: &[&str]) -> Vec<(String, io::Result<u64>)> {
+        names.iter().map(|n| { let p = dir.join(n); let len = std::fs::metadata(&p).map(|m| m.len()); (p.to_string_lossy().into_owned(), len) }).collect()
+    }
+
+    #[test]
+    fn an_existing_target_is_kept_unless_overwrite() {
+        let dir = scratch("exists");
+        let target = dir.join("out.zip");
+        std::fs::write(&target, "keep me").unwrap();
+        let t = target.to_str().unwrap();
+        let e = write_zip(t, false, None, &inputs(&dir, &["a.txt"]), 10, 1 << 20).unwrap_err();
+        assert!(e.ends_with("already exists (pass overwrite: true to replace it)"), "{}", e);
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "keep me");
+        assert!(write_zip(t, true, None, &inputs(&dir, &["a.txt"]), 10, 1 << 20).unwrap().contains("(1 files, 5 bytes"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn the_archive_is_not_added_to_itself() {
+        let dir = scratch("self");
+        std::fs::write(dir.join("out.zip"), "old archive").unwrap();
+        let target = dir.join("out.zip");
+        let r = write_zip(target.to_str().unwrap(), true, None, &inputs(&dir, &["a.txt", "out.zip", "b.txt"]), 10, 1 << 20).unwrap();
+        assert!(r.contains("(2 files, 10 bytes") && r.contains("out.zip (the archive itself)"), "{}", r);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn caps_skip_the_rest() {
+        let dir = scratch("caps");
+        let target = dir.join("out.zip");
+        let r = write_zip(target.to_str().unwrap(), false, None, &inputs(&dir, &["a.txt", "b.txt"]), 1, 1 << 20).unwrap();
+        assert!(r.contains("b.txt (file cap 1)"), "{}", r);
+        let r = write_zip(target.to_str().unwrap(), true, None, &inputs(&dir, &["a.txt", "b.txt"]), 10, 7).unwrap();
+        assert!(r.contains("b.txt (size cap 7 bytes)"), "{}", r);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
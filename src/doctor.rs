@@ -0,0 +1,135 @@
+//! Prerequisite checks behind `everything doctor` and everything_doctor: each check passes, fails with a fix,
+//! or is skipped because an earlier one failed
+
+use std::fmt::Write as _;
+
+use serde_json::json;
+
+use crate::{count, worker, Opts, SearchError, BACKEND};
+
+enum Outcome {
+    Pass(String),
+    Fail { detail: String, fix: String },
+    Skip(&'static str),
+}
+
+struct Check { name: &'static str, outcome: Outcome }
+
+/// Long enough for a busy Everything, short enough that a hung one doesn't hang the doctor (Windows only)
+const QUERY_TIMEOUT_MS: u32 = 5000;
+
+async fn checks() -> Vec<Check> {
+    if BACKEND.name() == "Everything" { everything_checks().await } else { fallback_checks().await }
+}
+
+/// Run every check and render them; the bool is whether all passed
+pub async fn run() -> (bool, String) {
+    let checks = checks().await;
+    let failed = checks.iter().filter(|c| matches!(c.outcome, Outcome::Fail { .. })).count();
+    let mut out = format!("everything-mcp-rs doctor (backend: {})\n\n", BACKEND.name());
+    for c in &checks {
+        let _ = match &c.outcome {
+            Outcome::Pass(d) => writeln!(out, "  [PASS] {}: {}", c.name, d),
+            Outcome::Fail { detail, fix } => writeln!(out, "  [FAIL] {}: {}\n         fix: {}", c.name, detail, fix),
+            Outcome::Skip(why) => writeln!(out, "  [SKIP] {}: {}", c.name, why),
+        };
+    }
+    let _ = if failed == 0 {
+        write!(out, "\nAll {} checks passed", checks.len())
+    } else {
+        write!(out, "\n{} of {} checks failed", failed, checks.len())
+    };
+    (failed == 0, out)
+}
+
+/// As run, rendered as {backend, passed, checks: [{name, status, detail, fix?}]} for `doctor --json`
+pub async fn run_json() -> (bool, String) {
+    let checks = checks().await;
+    let passed = !checks.iter().any(|c| matches!(c.outcome, Outcome::Fail { .. }));
+    let checks: Vec<_> = checks.iter().map(|c| match &c.outcome {
+        Outcome::Pass(d) => json!({ "name": c.name, "status": "pass", "detail": d }),
+        Outcome::Fail { detail, fix } => json!({ "name": c.name, "status": "fail", "detail": detail, "fix": fix }),
+        Outcome::Skip(why) => json!({ "name": c.name, "status": "skip", "detail": why }),
+    }).collect();
+    (passed, format!("{:#}", json!({ "backend": BACKEND.name(), "passed": passed, "checks": checks })))
+}
+
+async fn everything_checks() -> Vec<Check> {
+    let mut checks = Vec::new();
+    let loaded = worker::call(|g| g.as_ref().map(|dll| unsafe {
+        (dll.path.clone(), dll.get_ver.iter().map(|f| f()).collect::<Vec<u32>>(), (dll.is_loaded)() != 0)
+    }).map_err(Clone::clone)).await.unwrap_or_else(|e| Err(e.to_string()));
+    let (ver, db_loaded) = match loaded {
+        Ok((path, ver, db_loaded)) => {
+            checks.push(Check { name: "DLL found", outcome: Outcome::Pass(format!("loaded from {}", path)) });
+            (Some(ver), db_loaded)
+        }
+        Err(e) => {
+            checks.push(Check { name: "DLL found", outcome: Outcome::Fail {
+                detail: e,
+                fix: "Download the Everything SDK from voidtools.com and put Everything64.dll next to this program, \
+                    or point --dll-path / EVERYTHING_DLL_PATH at it".into(),
+            } });
+            (None, false)
+        }
+    };
+    // All zero when Everything isn't running or IPC is blocked
+    let running = ver.as_ref().is_some_and(|v| v[0] > 0);
+    checks.push(Check { name: "Everything running", outcome: match &ver {
+        None => Outcome::Skip("needs the DLL"),
+        Some(v) if running => Outcome::Pass(format!("v{}.{}.{}.{}", v[0], v[1], v[2], v[3])),
+        Some(_) => Outcome::Fail {
+            detail: "no reply over IPC".into(),
+            fix: "Start Everything (the full version: Everything Lite has no IPC). If Everything runs as administrator \
+                and this program doesn't, or the other way round, Windows blocks their IPC: run both at the same level".into(),
+        },
+    } });
+    checks.push(Check { name: "Database loaded", outcome: if !running {
+        Outcome::Skip("needs Everything running")
+    } else if db_loaded {
+        Outcome::Pass("index ready".into())
+    } else {
+        Outcome::Fail { detail: "the index is not loaded yet".into(), fix: "Wait for Everything to finish indexing, then run doctor again".into() }
+    } });
+    checks.push(Check { name: "Test query", outcome: if !running {
+        Outcome::Skip("needs Everything running")
+    } else {
+        // An empty query matches every indexed item; max 0 asks for the total only
+        test_query("", |n| format!("{} items indexed", n)).await
+    } });
+    checks
+}
+
+async fn fallback_checks() -> Vec<Check> {
+    let fix = match BACKEND.name() {
+        "locate" => "Install plocate or mlocate and run `sudo updatedb` to build its database",
+        "Spotlight (mdfind)" => "Check that Spotlight indexing is on with `mdutil -s /`",
+        _ => "Check that the EVERYTHING_MOCK_RESULTS file exists and is readable",
+    };
+    let ready = BACKEND.status();
+    let mut checks = vec![Check { name: "Backend ready", outcome: match &ready {
+        Ok(s) => Outcome::Pass(s.clone()),
+        Err(e) => Outcome::Fail { detail: e.to_string(), fix: fix.into() },
+    } }];
+    checks.push(Check { name: "Test query", outcome: if ready.is_err() {
+        Outcome::Skip("needs the backend")
+    } else {
+        // Any name will do; only whether the search runs matters
+        test_query("everything-mcp-rs", |n| format!("ran ({} matches)", n)).await
+    } });
+    checks
+}
+
+async fn test_query(q: &str, pass: impl FnOnce(u32) -> String) -> Outcome {
+    match count(q, &Opts { timeout_ms: QUERY_TIMEOUT_MS, ..Default::default() }).await {
+        Ok(n) => Outcome::Pass(pass(n)),
+        Err(SearchError::Timeout(ms)) => Outcome::Fail {
+            detail: format!("no answer within {} ms", ms),
+            fix: "Everything may be busy rebuilding its index; wait a minute and run doctor again".into(),
+        },
+        Err(e) => Outcome::Fail {
+            detail: e.to_string(),
+            fix: "Try the same search in Everything's own window; if that works, run with -v and report the log".into(),
+        },
+    }
+}
@@ -0,0 +1,292 @@
+//! Everything query tokenizer used by everything_explain
+
+use crate::filters::quote_escape;
+
+/// Search functions and modifiers Everything understands (lowercase, without the colon)
+const KNOWN: &[&str] = &[
+    "ext", "file", "files", "folder", "folders", "path", "nopath", "parent", "infolder", "nosubfolders",
+    "case", "nocase", "ww", "wholeword", "nowholeword", "wfn", "wholefilename", "regex", "noregex",
+    "diacritics", "nodiacritics", "ascii", "utf8", "noascii",
+    "size", "dm", "datemodified", "dc", "datecreated", "da", "dateaccessed", "dr", "daterun", "rc", "recentchange",
+    "attrib", "attributes", "empty", "dupe", "namepartdupe", "sizedupe", "dmdupe", "dcdupe",
+    "content", "ansicontent", "utf8content", "utf16content", "utf16becontent",
+    "len", "depth", "parents", "child", "childcount", "childfile", "childfolder", "startwith", "endwith",
+    "type", "runcount", "root", "shell", "count", "audio", "zip", "doc", "exe", "pic", "video",
+    "width", "height", "dimensions", "orientation", "bitdepth", "length", "album", "artist", "genre", "title", "track",
+    "comment", "year", "filelist", "frn", "fsi", "understandslashes", "sort",
+];
+
+#[derive(Debug, PartialEq)]
+pub enum Tok {
+    /// Plain search term, possibly with wildcards
+    Term(String),
+    /// Quoted phrase, outer quotes stripped and `""` read as one quote
+    Phrase(String),
+    /// `name:value` function or modifier; value may be empty, and a quoted value is unescaped like a phrase
+    Func(String, String),
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+/// Split a query into tokens, collecting syntax problems along the way
+pub fn tokenize(q: &str) -> (Vec<Tok>, Vec<String>) {
+    let (mut toks, mut errs) = (Vec::new(), Vec::new());
+    let cs: Vec<char> = q.chars().collect();
+    let mut i = 0;
+    while i < cs.len() {
+        match cs[i] {
+            c if c.is_whitespace() => { i += 1; }
+            '|' => { toks.push(Tok::Or); i += 1; }
+            '!' => { toks.push(Tok::Not); i += 1; }
+            '<' => { toks.push(Tok::Open); i += 1; }
+            '>' => { toks.push(Tok::Close); i += 1; }
+            '"' => {
+                let (open, mut text, mut closed) = (i, String::new(), false);
+                i += 1;
+                while i < cs.len() {
+                    // A quote inside a phrase is written twice
+                    if cs[i] == '"' && cs.get(i + 1) == Some(&'"') { text.push('"'); i += 2; continue; }
+                    if cs[i] == '"' { closed = true; i += 1; break; }
+                    text.push(cs[i]);
+                    i += 1;
+                }
+                if !closed { errs.push(format!("Unbalanced quote at position {}", open)); }
+                toks.push(Tok::Phrase(text));
+            }
+            _ => {
+                // Word runs to whitespace or a group/or marker; quotes inside a word extend it (e.g. path:"C:\x y").
+                // < and > straight after a colon are comparisons (size:>1mb), not groups.
+                let start = i;
+                let mut quoted = false;
+                while i < cs.len() && (quoted || !(cs[i].is_whitespace() || cs[i] == '|'
+                    || (matches!(cs[i], '<' | '>') && !matches!(cs[i - 1], ':' | '<' | '>' | '=')))) {
+                    if cs[i] == '"' { quoted = !quoted; }
+                    i += 1;
+                }
+                if quoted { errs.push(format!("Unbalanced quote in word at position {}", start)); }
+                let w: String = cs[start..i].iter().collect();
+                toks.push(word(&w));
+            }
+        }
+    }
+    (toks, errs)
+}
+
+fn word(w: &str) -> Tok {
+    // A drive letter like C:\ is a path, not a function
+    match w.find(':') {
+        Some(n) if n > 1 && w[..n].chars().all(|c| c.is_ascii_alphanumeric() || c == '-') => {
+            let v = &w[n + 1..];
+            let v = v.strip_prefix('"').and_then(|v| v.strip_suffix('"')).map_or_else(|| v.to_string(), |v| v.replace("\"\"", "\""));
+            Tok::Func(w[..n].to_lowercase(), v)
+        }
+        _ => Tok::Term(w.to_string()),
+    }
+}
+
+/// Structured breakdown of a query plus likely mistakes. Never runs the query.
+pub fn explain(q: &str) -> String {
+    let (toks, mut errs) = tokenize(q);
+    let mut out = format!("Query: {}\n\nTokens:\n", q);
+    let mut depth = 0i32;
+    for (i, t) in toks.iter().enumerate() {
+        let line = match t {
+            Tok::Term(w) if w.contains(['*', '?']) => format!("wildcard   {}  (* = any chars, ? = one char; matches whole name)", w),
+            Tok::Term(w) => format!("term       {}  (substring match)", w),
+            Tok::Phrase(p) => format!("phrase     \"{}\"  (exact text incl. spaces)", quote_escape(p)),
+            Tok::Func(f, v) => {
+                if !KNOWN.contains(&f.as_str()) { errs.push(format!("Unknown function '{}:'", f)); }
+                if v.is_empty() && needs_value(f) { errs.push(format!("'{}:' has no value", f)); }
+                format!("function   {}:{}  ({})", f, v, describe(f))
+            }
+            Tok::Or => {
+                if i == 0 || i + 1 == toks.len() || matches!(toks.get(i + 1), Some(Tok::Or | Tok::Close)) {
+                    errs.push(format!("Dangling OR at token {}", i + 1));
+                }
+                "or         |  (either side may match)".into()
+            }
+            Tok::Not => {
+                if matches!(toks.get(i + 1), None | Some(Tok::Or | Tok::Close)) { errs.push(format!("NOT with nothing to negate at token {}", i + 1)); }
+                "not        !  (exclude next term)".into()
+            }
+            Tok::Open => { depth += 1; "group      <".into() }
+            Tok::Close => {
+                depth -= 1;
+                if depth < 0 { errs.push(format!("Unmatched '>' at token {}", i + 1)); depth = 0; }
+                "group      >".into()
+            }
+        };
+        out.push_str(&format!("  {}\n", line));
+    }
+    if depth > 0 { errs.push(format!("{} unclosed '<' group(s)", depth)); }
+    if toks.is_empty() { errs.push("Empty query matches everything".into()); }
+    out.push_str("\nIssues:\n");
+    if errs.is_empty() { out.push_str("  none\n"); }
+    for e in &errs { out.push_str(&format!("  - {}\n", e)); }
+    out
+}
+
+fn render(t: &Tok) -> String {
+    match t {
+        Tok::Term(w) => w.clone(),
+        Tok::Phrase(p) => format!("\"{}\"", quote_escape(p)),
+        Tok::Func(f, v) if v.contains([' ', '"']) => format!("{}:\"{}\"", f, quote_escape(v)),
+        Tok::Func(f, v) => format!("{}:{}", f, v),
+        Tok::Or => "|".into(),
+        Tok::Not => "!".into(),
+        Tok::Open => "<".into(),
+        Tok::Close => ">".into(),
+    }
+}
+
+/// Broader variants of a query to try when it finds nothing, most conservative first:
+/// drop ext: filters, then match any plain term instead of all of them.
+/// Queries using OR, NOT or groups are left alone since loosening them changes their meaning.
+pub fn relaxations(q: &str) -> Vec<(&'static str, String)> {
+    let (toks, errs) = tokenize(q);
+    if !errs.is_empty() || toks.iter().any(|t| matches!(t, Tok::Or | Tok::Not | Tok::Open | Tok::Close)) { return Vec::new(); }
+    let mut out = Vec::new();
+    let no_ext: Vec<&Tok> = toks.iter().filter(|t| !matches!(t, Tok::Func(f, _) if f == "ext")).collect();
+    if no_ext.len() < toks.len() && !no_ext.is_empty() {
+        out.push(("dropped ext: filter", no_ext.iter().map(|t| render(t)).collect::<Vec<_>>().join(" ")));
+    }
+    let (terms, rest): (Vec<&Tok>, Vec<&Tok>) = no_ext.into_iter().partition(|t| matches!(t, Tok::Term(_) | Tok::Phrase(_)));
+    if terms.len() > 1 {
+        let any = terms.iter().map(|t| render(t)).collect::<Vec<_>>().join("|");
+        let rest: Vec<String> = rest.iter().map(|t| render(t)).collect();
+        out.push(("matched any term instead of all", format!("<{}> {}", any, rest.join(" ")).trim_end().to_string()));
+    }
+    out
+}
+
+/// Pin each plain term and phrase to the file name with nopath:, so neither a parent folder's name nor a
+/// term containing a separator (which Everything otherwise matches against the full path) can match
+pub fn name_only(q: &str) -> Result<String, String> {
+    let (toks, errs) = tokenize(q);
+    if let Some(e) = errs.into_iter().next() { return Err(e); }
+    let mut out = String::new();
+    for t in &toks {
+        if !out.is_empty() && !out.ends_with('!') { out.push(' '); }
+        if matches!(t, Tok::Term(_) | Tok::Phrase(_)) { out.push_str("nopath:"); }
+        out.push_str(&render(t));
+    }
+    Ok(out)
+}
+
+fn needs_value(f: &str) -> bool {
+    matches!(f, "ext" | "size" | "dm" | "datemodified" | "dc" | "datecreated" | "da" | "dateaccessed" | "attrib" | "attributes"
+        | "content" | "parent" | "infolder" | "len" | "depth" | "startwith" | "endwith" | "type" | "runcount")
+}
+
+fn describe(f: &str) -> &'static str {
+    match f {
+        "ext" => "extension list, separated by ;",
+        "size" => "file size, e.g. >1mb or 10kb..20kb",
+        "dm" | "datemodified" => "date modified",
+        "dc" | "datecreated" => "date created",
+        "da" | "dateaccessed" => "date accessed",
+        "dr" | "daterun" => "date last run",
+        "rc" | "recentchange" => "date recently changed",
+        "attrib" | "attributes" => "file attributes, e.g. H, R, S",
+        "file" | "files" => "files only",
+        "folder" | "folders" => "folders only",
+        "path" => "match against full path",
+        "nopath" => "match against name only",
+        "parent" | "infolder" => "direct children of folder",
+        "case" | "nocase" => "case sensitivity",
+        "ww" | "wholeword" | "nowholeword" => "whole word matching",
+        "wfn" | "wholefilename" => "match the whole file name",
+        "regex" | "noregex" => "regular expression",
+        "content" | "ansicontent" | "utf8content" | "utf16content" | "utf16becontent" => "file contents (slow)",
+        "empty" => "empty folders",
+        "dupe" | "namepartdupe" | "sizedupe" | "dmdupe" | "dcdupe" => "duplicates",
+        "len" => "filename length",
+        "depth" | "parents" => "folder depth",
+        "runcount" => "times opened via Everything",
+        "audio" | "zip" | "doc" | "exe" | "pic" | "video" => "built-in type macro",
+        _ if KNOWN.contains(&f) => "search modifier",
+        _ => "unrecognized",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toks(q: &str) -> Vec<Tok> {
+        let (t, errs) = tokenize(q);
+        assert!(errs.is_empty(), "{}: {:?}", q, errs);
+        t
+    }
+
+    fn term(s: &str) -> Tok { Tok::Term(s.into()) }
+    fn func(f: &str, v: &str) -> Tok { Tok::Func(f.into(), v.into()) }
+
+    #[test]
+    fn well_formed_queries_tokenize() {
+        assert_eq!(toks("report ext:pdf;docx dm:last7days"), [term("report"), func("ext", "pdf;docx"), func("dm", "last7days")]);
+        assert_eq!(toks(r#""annual report" !draft"#), [Tok::Phrase("annual report".into()), Tok::Not, term("draft")]);
+        assert_eq!(toks("<a | b> *.rs"), [Tok::Open, term("a"), Tok::Or, term("b"), Tok::Close, term("*.rs")]);
+        assert_eq!(toks("a|b"), [term("a"), Tok::Or, term("b")]);
+    }
+
+    #[test]
+    fn comparisons_are_not_groups() {
+        assert_eq!(toks("size:>1mb dm:>=2024-01-01 size:<10kb"), [func("size", ">1mb"), func("dm", ">=2024-01-01"), func("size", "<10kb")]);
+    }
+
+    #[test]
+    fn drive_paths_are_terms_and_quoted_values_stay_whole() {
+        assert_eq!(toks(r"C:\Users"), [term(r"C:\Users")]);
+        assert_eq!(toks(r#"path:"C:\My Docs" x"#), [func("path", r"C:\My Docs"), term("x")]);
+        assert_eq!(toks("DM:today file:"), [func("dm", "today"), func("file", "")]);
+    }
+
+    #[test]
+    fn doubled_quotes_are_one_quote() {
+        assert_eq!(toks(r#""say ""hi""""#), [Tok::Phrase(r#"say "hi""#.into())]);
+        assert_eq!(toks(r#"content:"a ""b"" c""#), [func("content", r#"a "b" c"#)]);
+        assert_eq!(toks(r#""" x"#), [Tok::Phrase(String::new()), term("x")]);
+    }
+
+    #[test]
+    fn unbalanced_quotes_are_reported() {
+        let (t, errs) = tokenize(r#"a "open phrase"#);
+        assert_eq!(t, [term("a"), Tok::Phrase("open phrase".into())]);
+        assert_eq!(errs, ["Unbalanced quote at position 2"]);
+        let (_, errs) = tokenize(r#"path:"C:\x y"#);
+        assert_eq!(errs, ["Unbalanced quote in word at position 0"]);
+    }
+
+    #[test]
+    fn explain_flags_mistakes() {
+        let issues = |q: &str| explain(q).split("Issues:\n").nth(1).unwrap().to_string();
+        assert_eq!(issues("report ext:pdf"), "  none\n");
+        assert!(issues("bogus:1").contains("Unknown function 'bogus:'"));
+        assert!(issues("ext:").contains("'ext:' has no value"));
+        assert!(issues("a |").contains("Dangling OR at token 2"));
+        assert!(issues("a !").contains("NOT with nothing to negate"));
+        assert!(issues("<a").contains("1 unclosed '<' group(s)"));
+        assert!(issues("a>").contains("Unmatched '>'"));
+        assert!(issues("").contains("Empty query matches everything"));
+    }
+
+    #[test]
+    fn relaxations_drop_ext_then_match_any_term() {
+        assert_eq!(relaxations("annual report ext:pdf"), [
+            ("dropped ext: filter", "annual report".to_string()),
+            ("matched any term instead of all", "<annual|report>".to_string()),
+        ]);
+        assert!(relaxations("a | b").is_empty());
+        assert!(relaxations(r#""open"#).is_empty());
+    }
+
+    #[test]
+    fn name_only_pins_terms_to_the_name() {
+        assert_eq!(name_only(r#"src "a b" ext:rs !tmp"#).unwrap(), r#"nopath:src nopath:"a b" ext:rs !nopath:tmp"#);
+        assert_eq!(name_only(r#""x ""y""""#).unwrap(), r#"nopath:"x ""y""""#);
+        assert!(name_only(r#""open"#).is_err());
+    }
+}
@@ -0,0 +1,360 @@
+//! Validation for filter values that tools splice into Everything functions (dm:, dc:, size:)
+
+const DATE_FORMATS: &str = "today, yesterday, thisweek/thismonth/thisyear, lastweek/lastmonth/lastyear, \
+    lastNdays (also hours/weeks/months/years), YYYY, YYYY-MM, YYYY-MM-DD[THH:MM[:SS]], \
+    an optional >, >=, <, <= or = prefix, or a range A..B";
+
+fn is_keyword(v: &str) -> bool {
+    if matches!(v, "today" | "yesterday" | "thisweek" | "thismonth" | "thisyear" | "lastweek" | "lastmonth" | "lastyear") {
+        return true;
+    }
+    // last<N><unit>, e.g. last7days
+    let Some(rest) = v.strip_prefix("last") else { return false };
+    let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+    digits > 0 && matches!(&rest[digits..], "minutes" | "hours" | "days" | "weeks" | "months" | "years")
+}
+
+fn is_iso(v: &str) -> bool {
+    let (date, time) = v.split_once(['T', 't']).map_or((v, None), |(d, t)| (d, Some(t)));
+    let nums = |s: &str, lens: &[usize]| {
+        let parts: Vec<&str> = s.split(['-', ':']).collect();
+        parts.len() <= lens.len() && parts.iter().zip(lens).all(|(p, &n)| p.len() == n && p.bytes().all(|b| b.is_ascii_digit()))
+    };
+    // A time needs a full date in front of it
+    nums(date, &[4, 2, 2]) && time.is_none_or(|t| date.len() == 10 && t.len() >= 5 && nums(t, &[2, 2, 2]))
+}
+
+fn is_date(v: &str) -> bool {
+    let v = v.to_ascii_lowercase();
+    is_keyword(&v) || is_iso(&v)
+}
+
+/// Accept the date filter forms Everything understands, with a list of them on error
+pub fn check_date(f: &str) -> Result<(), String> {
+    let f = f.trim();
+    let ok = match f.split_once("..") {
+        Some((a, b)) => is_date(a) && is_date(b),
+        None => is_date(f.trim_start_matches(['>', '<', '='])),
+    };
+    if ok { Ok(()) } else { Err(format!("Unrecognized date filter '{}'. Valid forms: {}. Pass unchecked: true to send it as-is.", f, DATE_FORMATS)) }
+}
+
+/// Normalize a human size ("1.5gb", "500 MB", "2gigs", "1024") to Everything's form ("1536mb", "500mb", "2gb", "1024").
+/// Fractions step down a unit until whole, falling back to bytes.
+pub fn parse_size(s: &str) -> Result<String, String> {
+    const UNITS: [&str; 5] = ["", "kb", "mb", "gb", "tb"];
+    let (mut v, mut pow) = size_parts(s)?;
+    while v.fract() != 0.0 && pow > 0 { v *= 1024.0; pow -= 1; }
+    Ok(format!("{}{}", v.round() as u64, UNITS[pow]))
+}
+
+/// A human size in bytes, e.g. "1.5kb" -> 1536
+pub fn size_bytes(s: &str) -> Result<u64, String> {
+    let (v, pow) = size_parts(s)?;
+    Ok((v * 1024f64.powi(pow as i32)).round() as u64)
+}
+
+/// Number and power of 1024 of a human size
+fn size_parts(s: &str) -> Result<(f64, usize), String> {
+    let t = s.trim().to_ascii_lowercase();
+    let split = t.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(t.len());
+    let (num, unit) = (&t[..split], t[split..].trim());
+    let v: f64 = num.parse().map_err(|_| format!("Invalid size '{}': expected a number with an optional unit like 500mb or 1.5 GB", s))?;
+    let pow = match unit.trim_end_matches('s') {
+        "" | "b" | "byte" => 0,
+        "k" | "kb" | "kib" | "kilobyte" => 1,
+        "m" | "mb" | "mib" | "meg" | "megabyte" => 2,
+        "g" | "gb" | "gib" | "gig" | "gigabyte" => 3,
+        "t" | "tb" | "tib" | "terabyte" => 4,
+        _ => return Err(format!("Unknown size unit '{}' in '{}' (use b, kb, mb, gb or tb)", unit, s)),
+    };
+    Ok((v, pow))
+}
+
+/// Validate a size: filter, normalizing each size in it: 1.5gb, >500 mb, <=10kb, 1mb..2mb, or a keyword like huge
+pub fn size_filter(f: &str) -> Result<String, String> {
+    let f = f.trim();
+    let lower = f.to_ascii_lowercase();
+    if matches!(lower.as_str(), "empty" | "tiny" | "small" | "medium" | "large" | "huge" | "gigantic" | "unknown") { return Ok(lower); }
+    if let Some((a, b)) = f.split_once("..") { return Ok(format!("{}..{}", parse_size(a)?, parse_size(b)?)); }
+    let value = f.trim_start_matches(['>', '<', '=']);
+    Ok(format!("{}{}", &f[..f.len() - value.len()], parse_size(value)?))
+}
+
+/// Compile a regex with the regex crate so syntax errors come back with their position instead of Everything's
+/// bare "Query failed". Everything's engine is a different flavor: this rejects lookaround and backreferences,
+/// and a pattern that passes can still fail there, so it is a pre-check, not a guarantee
+pub fn check_regex(pattern: &str) -> Result<(), String> {
+    match regex::Regex::new(pattern) {
+        Ok(_) => Ok(()),
+        Err(regex::Error::Syntax(e)) => Err(format!("Invalid regex (pass unchecked: true to send it anyway):\n{}", e)),
+        Err(e) => Err(format!("Invalid regex: {}", e)),
+    }
+}
+
+/// size: filters for an inclusive range with either end open: "size:>=10mb size:<=100mb"
+pub fn size_range(min: Option<&str>, max: Option<&str>) -> Result<String, String> {
+    let (min, max) = (min.map(str::trim).filter(|s| !s.is_empty()), max.map(str::trim).filter(|s| !s.is_empty()));
+    if let (Some(a), Some(b)) = (min, max) {
+        if size_bytes(a)? > size_bytes(b)? { return Err(format!("min {} is larger than max {}", a, b)); }
+    }
+    let mut parts = Vec::new();
+    if let Some(a) = min { parts.push(format!("size:>={}", parse_size(a)?)); }
+    if let Some(b) = max { parts.push(format!("size:<={}", parse_size(b)?)); }
+    if parts.is_empty() { return Err("give min, max or both".into()); }
+    Ok(parts.join(" "))
+}
+
+const RECENT_PHRASES: &str = "today, yesterday, this week/month/year, last week/month/year (calendar), \
+    last/past hour, last/past N minutes/hours/days/weeks/months/years, N days/weeks ago, \
+    since monday..sunday, since yesterday, since YYYY-MM-DD";
+
+/// Translate "last hour", "past 3 weeks", "since monday", ... into an Everything date value for dm:/dc:.
+/// `today` anchors weekday and "ago" phrases.
+pub fn recent_phrase(phrase: &str, today: chrono::NaiveDate) -> Result<String, String> {
+    use chrono::Datelike;
+    let err = || format!("Unrecognized phrase '{}'. Supported: {}", phrase.trim(), RECENT_PHRASES);
+    let p = phrase.trim().to_ascii_lowercase();
+    // Filler words carry no meaning here: "in the last 24 hours" == "last 24 hours"
+    let words: Vec<&str> = p.split_whitespace().filter(|w| !matches!(*w, "in" | "within" | "the")).collect();
+    let unit = |w: &str| -> Option<&'static str> {
+        Some(match w.trim_end_matches('s') {
+            "minute" | "min" => "minutes",
+            "hour" | "hr" => "hours",
+            "day" => "days",
+            "week" => "weeks",
+            "month" => "months",
+            "year" => "years",
+            _ => return None,
+        })
+    };
+    let n = |w: &str| w.parse::<u32>().ok().filter(|&n| n > 0);
+    Ok(match words.as_slice() {
+        ["today"] => "today".into(),
+        ["yesterday"] => "yesterday".into(),
+        ["this", u @ ("week" | "month" | "year")] => format!("this{}", u),
+        ["last", u @ ("week" | "month" | "year")] => format!("last{}", u),
+        ["last" | "past", u] => format!("last1{}", unit(u).ok_or_else(err)?),
+        ["last" | "past", c, u] => format!("last{}{}", n(c).ok_or_else(err)?, unit(u).ok_or_else(err)?),
+        [c, u, "ago"] => {
+            let c = n(c).ok_or_else(err)? as i64;
+            let day = match unit(u) {
+                Some("days") => today - chrono::Duration::days(c),
+                Some("weeks") => today - chrono::Duration::weeks(c),
+                _ => return Err(err()),
+            };
+            day.format("%Y-%m-%d").to_string()
+        }
+        ["since", "yesterday"] => ">=yesterday".into(),
+        ["since", "today"] => "today".into(),
+        ["since", d] => match d.parse::<chrono::Weekday>() {
+            // Most recent such day, today included
+            Ok(wd) => {
+                let back = (7 + today.weekday().num_days_from_monday() - wd.num_days_from_monday()) % 7;
+                format!(">={}", (today - chrono::Duration::days(back as i64)).format("%Y-%m-%d"))
+            }
+            Err(_) if is_iso(d) => format!(">={}", d),
+            Err(_) => return Err(err()),
+        },
+        _ => return Err(err()),
+    })
+}
+
+const DURATION_UNITS: &str = "min (minutes), h (hours), d (days), w (weeks), mo (months), y (years)";
+
+/// The moment `duration` ("7d", "3w", "6mo", "2h") before `now`. Months and years are calendar steps,
+/// so 1mo before March 31 is February's last day
+pub fn duration_before(duration: &str, now: chrono::NaiveDateTime) -> Result<chrono::NaiveDateTime, String> {
+    let d = duration.trim().to_ascii_lowercase();
+    let digits = d.chars().take_while(|c| c.is_ascii_digit()).count();
+    let n: u32 = d[..digits].parse().ok().filter(|&n| n > 0 && digits < d.len())
+        .ok_or_else(|| format!("Invalid duration '{}': expected a positive number and a unit, like 7d or 6mo", duration.trim()))?;
+    let cutoff = match d[digits..].trim() {
+        "min" | "mins" | "minute" | "minutes" => now.checked_sub_signed(chrono::Duration::minutes(n.into())),
+        "h" | "hr" | "hrs" | "hour" | "hours" => now.checked_sub_signed(chrono::Duration::hours(n.into())),
+        "d" | "day" | "days" => now.checked_sub_signed(chrono::Duration::days(n.into())),
+        "w" | "wk" | "week" | "weeks" => now.checked_sub_signed(chrono::Duration::weeks(n.into())),
+        "mo" | "month" | "months" => now.checked_sub_months(chrono::Months::new(n)),
+        "y" | "yr" | "year" | "years" => n.checked_mul(12).and_then(|m| now.checked_sub_months(chrono::Months::new(m))),
+        u => return Err(format!("Unknown duration unit '{}' in '{}'. Units: {}", u, duration.trim(), DURATION_UNITS)),
+    };
+    cutoff.ok_or_else(|| format!("Duration '{}' is too far back", duration.trim()))
+}
+
+/// Clean a folder path for a quoted `"folder\"` term: trims whitespace, surrounding quotes and trailing
+/// separators. Pass the result through quote_escape before quoting it.
+pub fn normalize_folder(path: &str) -> Result<String, String> {
+    let p = path.trim();
+    let p = p.strip_prefix('"').and_then(|p| p.strip_suffix('"')).unwrap_or(p).trim();
+    let p = p.trim_end_matches(['\\', '/']);
+    if p.is_empty() { return Err("folder path is empty".into()); }
+    Ok(p.to_string())
+}
+
+/// Make a value safe inside an Everything `"..."` string, where a quote is written as `""`.
+/// Backslashes are not escapes in Everything and pass through as-is.
+pub fn quote_escape(s: &str) -> String {
+    s.replace('"', "\"\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_accept_keywords_iso_comparisons_and_ranges() {
+        for ok in ["today", "THISWEEK", "last7days", "last12hours", "2024", "2024-03", "2024-03-05",
+            "2024-03-05T14:30", "2024-03-05t14:30:15", ">=2024-01-01", "<lastweek", "=yesterday",
+            "2024-01-01..2024-02-01", " lastyear "] {
+            assert!(check_date(ok).is_ok(), "{}", ok);
+        }
+    }
+
+    #[test]
+    fn dates_reject_other_forms_and_list_the_valid_ones() {
+        for bad in ["", "tomorrow", "last0", "lastdays", "last7fortnights", "24-03-05", "2024-3-5", "2024-03-05T1",
+            "2024T14:30", "2024-03-05T14:30:15:00", "2024..soon", "03/05/2024"] {
+            assert!(check_date(bad).is_err(), "{}", bad);
+        }
+        let e = check_date("soon").unwrap_err();
+        assert!(e.contains("'soon'") && e.contains(DATE_FORMATS) && e.contains("unchecked: true"), "{}", e);
+    }
+
+    #[test]
+    fn sizes_normalize_to_whole_units() {
+        for (input, want) in [("1.5gb", "1536mb"), ("500 MB", "500mb"), ("2gigs", "2gb"), ("1024", "1024"),
+            ("10 kilobytes", "10kb"), ("0.5kb", "512"), ("1tb", "1tb"), ("3 bytes", "3"), ("1.5 MiB", "1536kb")] {
+            assert_eq!(parse_size(input).unwrap(), want, "{}", input);
+        }
+    }
+
+    #[test]
+    fn sizes_reject_missing_numbers_and_unknown_units() {
+        assert!(parse_size("").unwrap_err().contains("expected a number"));
+        assert!(parse_size("mb").unwrap_err().contains("expected a number"));
+        assert!(parse_size("1.2.3mb").unwrap_err().contains("expected a number"));
+        assert!(parse_size("-5mb").unwrap_err().contains("expected a number"));
+        assert!(parse_size("5pb").unwrap_err().contains("Unknown size unit 'pb'"));
+    }
+
+    #[test]
+    fn size_bytes_scales_by_1024() {
+        assert_eq!(size_bytes("1.5kb"), Ok(1536));
+        assert_eq!(size_bytes("2 GB"), Ok(2 << 30));
+        assert_eq!(size_bytes("7"), Ok(7));
+        assert!(size_bytes("lots").is_err());
+    }
+
+    #[test]
+    fn size_filters_keep_the_comparison_and_keywords() {
+        assert_eq!(size_filter(">500 MB").unwrap(), ">500mb");
+        assert_eq!(size_filter("<=1.5gb").unwrap(), "<=1536mb");
+        assert_eq!(size_filter("1mb..2mb").unwrap(), "1mb..2mb");
+        assert_eq!(size_filter("Huge").unwrap(), "huge");
+        assert!(size_filter(">big").is_err());
+    }
+
+    #[test]
+    fn recent_phrases_translate_to_date_values() {
+        // A Wednesday
+        let today = chrono::NaiveDate::from_ymd_opt(2024, 3, 6).unwrap();
+        for (phrase, want) in [
+            ("today", "today"), ("Yesterday", "yesterday"), ("this month", "thismonth"), ("last week", "lastweek"),
+            ("last hour", "last1hours"), ("past day", "last1days"), ("in the last 24 hours", "last24hours"),
+            ("past 3 weeks", "last3weeks"), ("within the last 15 mins", "last15minutes"), ("last 2 years", "last2years"),
+            ("3 days ago", "2024-03-03"), ("2 weeks ago", "2024-02-21"),
+            ("since monday", ">=2024-03-04"), ("since Wednesday", ">=2024-03-06"), ("since thursday", ">=2024-02-29"),
+            ("since yesterday", ">=yesterday"), ("since today", "today"), ("since 2024-01-15", ">=2024-01-15"),
+        ] {
+            assert_eq!(recent_phrase(phrase, today).unwrap(), want, "{}", phrase);
+        }
+    }
+
+    #[test]
+    fn recent_phrases_reject_the_rest_with_the_supported_list() {
+        let today = chrono::NaiveDate::from_ymd_opt(2024, 3, 6).unwrap();
+        for bad in ["", "last 0 days", "past 3 fortnights", "3 months ago", "since march", "next week", "last few days"] {
+            let e = recent_phrase(bad, today).unwrap_err();
+            assert!(e.contains(RECENT_PHRASES), "{}: {}", bad, e);
+        }
+    }
+
+    #[test]
+    fn folders_lose_whitespace_quotes_and_trailing_separators() {
+        for (input, want) in [(r"C:\Users\me", r"C:\Users\me"), (r"  C:\Program Files\  ", r"C:\Program Files"),
+            (r#""C:\My Docs\""#, r"C:\My Docs"), (r#" " D:\x " "#, r"D:\x"), ("D:/src//", "D:/src"),
+            (r"\\server\share\", r"\\server\share"), (r"C:\", "C:"), (r#"C:\it's "here""#, r#"C:\it's "here""#)] {
+            assert_eq!(normalize_folder(input).unwrap(), want, "{}", input);
+        }
+    }
+
+    #[test]
+    fn folders_that_are_only_separators_or_quotes_are_empty() {
+        for bad in ["", "   ", r"\", "//", r#""""#, r#"" \ ""#] {
+            assert_eq!(normalize_folder(bad).unwrap_err(), "folder path is empty", "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn quotes_double_and_backslashes_pass_through() {
+        assert_eq!(quote_escape("plain text"), "plain text");
+        assert_eq!(quote_escape(r#"say "hi""#), r#"say ""hi"""#);
+        assert_eq!(quote_escape(r#""""#), r#""""""#);
+        assert_eq!(quote_escape(r"C:\a\b\"), r"C:\a\b\");
+        assert_eq!(quote_escape(r#"a\"b"#), r#"a\""b"#);
+        assert_eq!(quote_escape(""), "");
+    }
+
+    #[test]
+    fn regexes_with_syntax_errors_say_where() {
+        for ok in [r"^report_\d{4}\.pdf$", r"(a|b)+", r"[^\\]*\.txt", ""] {
+            assert!(check_regex(ok).is_ok(), "{}", ok);
+        }
+        for bad in ["(unclosed", "closed)", "[abc", "a{2,1}", "*start", r"\"] {
+            let e = check_regex(bad).unwrap_err();
+            assert!(e.starts_with("Invalid regex (pass unchecked: true"), "{}: {}", bad, e);
+            assert!(e.contains(bad), "{}: {}", bad, e);
+        }
+        // A different flavor: lookaround is Everything's to judge, so unchecked is the way through
+        assert!(check_regex(r"foo(?=bar)").is_err());
+    }
+
+    #[test]
+    fn size_ranges_take_either_end_or_both() {
+        assert_eq!(size_range(Some("10 MB"), Some("1.5gb")).unwrap(), "size:>=10mb size:<=1536mb");
+        assert_eq!(size_range(Some("1kb"), None).unwrap(), "size:>=1kb");
+        assert_eq!(size_range(Some("  "), Some("2gb")).unwrap(), "size:<=2gb");
+        assert_eq!(size_range(Some("1mb"), Some("1024kb")).unwrap(), "size:>=1mb size:<=1024kb");
+    }
+
+    #[test]
+    fn size_ranges_reject_inverted_empty_and_bad_ends() {
+        assert_eq!(size_range(Some("2gb"), Some("1500mb")).unwrap_err(), "min 2gb is larger than max 1500mb");
+        assert_eq!(size_range(None, Some("")).unwrap_err(), "give min, max or both");
+        assert!(size_range(Some("big"), Some("1gb")).unwrap_err().contains("Invalid size 'big'"));
+        assert!(size_range(None, Some("5pb")).unwrap_err().contains("Unknown size unit"));
+    }
+
+    #[test]
+    fn durations_step_back_from_now() {
+        let at = |s: &str| chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        let now = at("2024-03-31 12:00");
+        for (d, want) in [("30min", "2024-03-31 11:30"), ("2h", "2024-03-31 10:00"), ("7d", "2024-03-24 12:00"),
+            (" 3 Weeks ", "2024-03-10 12:00"), ("1mo", "2024-02-29 12:00"), ("13mo", "2023-02-28 12:00"),
+            ("1y", "2023-03-31 12:00"), ("2 years", "2022-03-31 12:00")] {
+            assert_eq!(duration_before(d, now).unwrap(), at(want), "{}", d);
+        }
+    }
+
+    #[test]
+    fn durations_need_a_positive_count_and_a_known_unit() {
+        let now = chrono::NaiveDate::from_ymd_opt(2024, 3, 31).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        for bad in ["", "7", "d", "0d", "-3d"] {
+            assert!(duration_before(bad, now).unwrap_err().starts_with("Invalid duration"), "{}", bad);
+        }
+        let e = duration_before("3fortnights", now).unwrap_err();
+        assert!(e.contains("'fortnights'") && e.contains(DURATION_UNITS), "{}", e);
+        assert!(duration_before("1.5d", now).unwrap_err().contains("Unknown duration unit '.5d'"));
+        assert!(duration_before("5000000y", now).unwrap_err().contains("too far back"));
+    }
+}
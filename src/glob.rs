@@ -0,0 +1,196 @@
+//! Glob pattern translation into Everything search syntax
+
+use crate::query::QueryBuilder;
+
+fn quote(s: &str) -> String {
+    if s.contains(' ') { format!("\"{}\"", s) } else { s.to_string() }
+}
+
+/// Expand one `{a,b}` group in a name pattern into Everything's `<a|b>` OR group
+fn name_pattern(name: &str) -> Result<String, String> {
+    if name.contains(['[', ']']) { return Err("Character classes ([...]) are not supported".into()); }
+    match (name.find('{'), name.find('}')) {
+        (None, None) => Ok(quote(name)),
+        (Some(o), Some(c)) if o < c && !name[c + 1..].contains(['{', '}']) => {
+            let (pre, post) = (&name[..o], &name[c + 1..]);
+            let alts: Vec<String> = name[o + 1..c].split(',').map(|a| quote(&format!("{}{}{}", pre, a.trim(), post))).collect();
+            Ok(format!("<{}>", alts.join("|")))
+        }
+        _ => Err("Unbalanced or nested {} in glob (only one {a,b} group is supported)".into()),
+    }
+}
+
+/// Translate a glob relative to `folder` into an Everything query.
+///
+/// A `**` segment makes the search recursive from the folder part before it; directories after
+/// `**` become a path substring (`\dir\`), so `a/**/b/*.rs` also matches `b` directly under `a`
+/// and anything deeper. Without `**`, the match is limited to one folder via `parent:`.
+pub fn to_query(folder: &str, glob: &str) -> Result<String, String> {
+    let folder = folder.trim().trim_end_matches(['\\', '/']);
+    if folder.is_empty() { return Err("folder is empty".into()); }
+    let glob = glob.trim().replace('\\', "/");
+    if glob.is_empty() { return Err("glob is empty".into()); }
+    if glob.starts_with('/') || glob.contains(':') { return Err("glob must be relative to the folder".into()); }
+    if glob.contains('"') { return Err("glob must not contain quotes".into()); }
+
+    let segs: Vec<&str> = glob.split('/').filter(|s| !s.is_empty() && *s != ".").collect();
+    if segs.contains(&"..") { return Err("glob must not contain '..'".into()); }
+    let (name, dirs) = segs.split_last().ok_or("glob has no file name part")?;
+    if *name == "**" { return Err("glob must end in a file name pattern, not **".into()); }
+    if dirs.iter().any(|d| d.contains(['*', '?', '{', '[']) && *d != "**") {
+        return Err("wildcards are only supported in the file name and as a whole ** segment".into());
+    }
+    let name = name_pattern(name)?;
+
+    // The builder quotes the folder and keeps a drive root's separator
+    let q = QueryBuilder::new().files_only();
+    let q = match dirs.iter().position(|d| *d == "**") {
+        Some(n) => {
+            let scope: String = dirs[..n].iter().map(|d| format!("\\{}", d)).collect();
+            let below: Vec<&str> = dirs[n + 1..].iter().copied().filter(|d| *d != "**").collect();
+            let q = q.in_folder(&format!("{}{}", folder, scope));
+            if below.is_empty() { q } else { q.raw(&format!("\"\\{}\\\"", below.join("\\"))) }
+        }
+        None => {
+            let sub: String = dirs.iter().map(|d| format!("\\{}", d)).collect();
+            q.parent(&format!("{}{}", folder, sub))
+        }
+    };
+    q.raw(&name).build()
+}
+
+/// One glob path segment as a regex over a single path component: `*` and `?` stop at `\`, each `{a,b}` group
+/// becomes an alternation
+fn segment_regex(seg: &str) -> Result<String, String> {
+    if seg.contains("**") { return Err("** must be a whole path segment, as in src/**/*.rs".into()); }
+    let mut out = String::new();
+    let mut group = false;
+    for c in seg.chars() {
+        match c {
+            '*' => out.push_str(r"[^\\]*"),
+            '?' => out.push_str(r"[^\\]"),
+            '{' if !group => { group = true; out.push_str("(?:"); }
+            '}' if group => { group = false; out.push(')'); }
+            ',' if group => out.push('|'),
+            '{' | '}' => return Err("Unbalanced or nested {} in glob".into()),
+            c => out.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    if group { return Err("Unbalanced or nested {} in glob".into()); }
+    Ok(out)
+}
+
+/// Translate a glob matched anywhere in the index into an Everything query, with whether it needs match path on.
+///
+/// `*.rs` and `**/*.rs` are a plain file name search. Folder segments turn the glob into a regex over the full path,
+/// anchored at a folder boundary, so `src/*.toml` matches `...\src\Cargo.toml` but not `...\src\a\b.toml` or
+/// `...\mysrc\x.toml` (Everything's own `*` would cross folders, and a wildcard term with match path on must
+/// match the whole path)
+pub fn glob_to_query(glob: &str) -> Result<(String, bool), String> {
+    let glob = glob.trim().replace('\\', "/");
+    if glob.is_empty() { return Err("glob is empty".into()); }
+    if glob.starts_with('/') || glob.contains(':') {
+        return Err("glob must be relative; use everything_search_by_glob_in_folder for a glob under a known folder".into());
+    }
+    if glob.starts_with('!') { return Err("Negated globs (!pattern) are not supported".into()); }
+    if ["?(", "*(", "+(", "@(", "!("].iter().any(|e| glob.contains(e)) {
+        return Err("Extended globs like +(...) and !(...) are not supported".into());
+    }
+    if glob.contains('"') { return Err("glob must not contain quotes".into()); }
+    if glob.contains(['[', ']']) { return Err("Character classes ([...]) are not supported".into()); }
+
+    let segs: Vec<&str> = glob.split('/').filter(|s| !s.is_empty() && *s != ".").collect();
+    if segs.contains(&"..") { return Err("glob must not contain '..'".into()); }
+    let (name, dirs) = segs.split_last().ok_or("glob has no file name part")?;
+    if *name == "**" { return Err("glob must end in a file name pattern, not **".into()); }
+    // Matching at any folder boundary already covers a leading **
+    let dirs: Vec<&str> = dirs.iter().copied().skip_while(|d| *d == "**").collect();
+    if dirs.is_empty() { return Ok((format!("file: {}", name_pattern(name)?), false)); }
+
+    let mut re = String::from(r"(?:^|\\)");
+    for d in dirs {
+        if d == "**" { re.push_str(r"(?:[^\\]*\\)*"); } else { re.push_str(&segment_regex(d)?); re.push_str(r"\\"); }
+    }
+    re.push_str(&segment_regex(name)?);
+    re.push('$');
+    Ok((format!("file: regex:\"{}\"", re), true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_only_glob_lists_one_folder() {
+        assert_eq!(to_query(r"C:\src\", "*.rs").unwrap(), r#"file: parent:"C:\src" *.rs"#);
+        assert_eq!(to_query("C:/src", "a/b/*.rs").unwrap(), r#"file: parent:"C:/src\a\b" *.rs"#);
+    }
+
+    #[test]
+    fn drive_root_keeps_its_separator() {
+        assert_eq!(to_query(r"C:\", "*.log").unwrap(), r#"file: parent:"C:\" *.log"#);
+        assert_eq!(to_query("D:", "**/*.log").unwrap(), r#"file: "D:\" *.log"#);
+    }
+
+    #[test]
+    fn double_star_searches_the_subtree() {
+        assert_eq!(to_query(r"C:\p", "**/*.rs").unwrap(), r#"file: "C:\p\" *.rs"#);
+        assert_eq!(to_query(r"C:\p", "src/**/*.rs").unwrap(), r#"file: "C:\p\src\" *.rs"#);
+        assert_eq!(to_query(r"C:\p", "a/**/b/c/*.rs").unwrap(), r#"file: "C:\p\a\" "\b\c\" *.rs"#);
+    }
+
+    #[test]
+    fn brace_group_becomes_an_or_group() {
+        assert_eq!(to_query(r"C:\p", "*.{rs,toml}").unwrap(), r#"file: parent:"C:\p" <*.rs|*.toml>"#);
+        assert_eq!(to_query(r"C:\p", "{read me,notes}.md").unwrap(), r#"file: parent:"C:\p" <"read me.md"|notes.md>"#);
+        assert!(to_query(r"C:\p", "{a,{b,c}}.md").is_err());
+    }
+
+    #[test]
+    fn folder_with_spaces_or_quotes_is_quoted() {
+        assert_eq!(to_query(r"C:\My Docs", "*.pdf").unwrap(), r#"file: parent:"C:\My Docs" *.pdf"#);
+        assert_eq!(to_query(r#"C:\say "hi""#, "**/*.txt").unwrap(), r#"file: "C:\say ""hi""\" *.txt"#);
+    }
+
+    #[test]
+    fn unsupported_globs_are_rejected() {
+        for (folder, glob) in [("", "*.rs"), (r"C:\p", ""), (r"C:\p", "/abs/*.rs"), (r"C:\p", "C:/x/*.rs"),
+            (r"C:\p", "../*.rs"), (r"C:\p", "src/**"), (r"C:\p", "s*c/*.rs"), (r"C:\p", "[ab].rs"), (r"C:\p", r#"a"b.rs"#)] {
+            assert!(to_query(folder, glob).is_err(), "{} / {}", folder, glob);
+        }
+    }
+
+    #[test]
+    fn name_globs_search_names_only() {
+        assert_eq!(glob_to_query("*.rs").unwrap(), ("file: *.rs".to_string(), false));
+        assert_eq!(glob_to_query("**/*.rs").unwrap(), ("file: *.rs".to_string(), false));
+        assert_eq!(glob_to_query("./**/**/*.{rs,toml}").unwrap(), ("file: <*.rs|*.toml>".to_string(), false));
+    }
+
+    #[test]
+    fn folder_globs_become_an_anchored_path_regex() {
+        let (q, path) = glob_to_query("src/*.toml").unwrap();
+        assert!(path);
+        assert_eq!(q, r#"file: regex:"(?:^|\\)src\\[^\\]*\.toml$""#);
+        assert_eq!(glob_to_query(r"src\**\test?.{rs,md}").unwrap().0, r#"file: regex:"(?:^|\\)src\\(?:[^\\]*\\)*test[^\\]\.(?:rs|md)$""#);
+    }
+
+    #[test]
+    fn folder_regex_matches_at_folder_boundaries() {
+        let (q, _) = glob_to_query("src/**/*.toml").unwrap();
+        let re = regex::Regex::new(q.strip_prefix("file: regex:\"").unwrap().strip_suffix('"').unwrap()).unwrap();
+        for p in [r"C:\p\src\Cargo.toml", r"C:\p\src\a\b\x.toml", r"src\x.toml"] { assert!(re.is_match(p), "{}", p); }
+        for p in [r"C:\p\mysrc\x.toml", r"C:\p\src\x.toml.bak", r"C:\p\src.toml"] { assert!(!re.is_match(p), "{}", p); }
+        let (q, _) = glob_to_query("src/*.toml").unwrap();
+        let re = regex::Regex::new(q.strip_prefix("file: regex:\"").unwrap().strip_suffix('"').unwrap()).unwrap();
+        assert!(re.is_match(r"C:\p\src\Cargo.toml") && !re.is_match(r"C:\p\src\a\b.toml"));
+    }
+
+    #[test]
+    fn unsupported_anywhere_globs_are_rejected() {
+        for glob in ["", "/src/*.rs", "C:/src/*.rs", "!*.rs", "+(a|b).rs", "*.@(rs|md)", r#"a"b"#, "[ab].rs",
+            "../x.rs", "src/**", "src/a**/x.rs", "{a,b.rs", "a}.rs"] {
+            assert!(glob_to_query(glob).is_err(), "{}", glob);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Byte-identical file detection for everything_find_identical: group by size, then SHA-256 only the collisions
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Read};
+
+use sha2::{Digest, Sha256};
+
+use crate::format_size;
+
+/// Files worth hashing: those sharing a size with another, largest sizes first while their total fits `budget`.
+/// Returns the candidates and how many collision files were left out by the budget
+pub fn candidates(files: Vec<(String, u64)>, budget: u64) -> (Vec<(String, u64)>, usize) {
+    let mut by_size: HashMap<u64, Vec<String>> = HashMap::new();
+    // Empty files are trivially identical and not worth reporting
+    for (path, size) in files.into_iter().filter(|(_, s)| *s > 0) { by_size.entry(size).or_default().push(path); }
+    let mut groups: Vec<(u64, Vec<String>)> = by_size.into_iter().filter(|(_, v)| v.len() > 1).collect();
+    groups.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    let (mut picked, mut skipped, mut used) = (Vec::new(), 0, 0u64);
+    for (size, paths) in groups {
+        let cost = size.saturating_mul(paths.len() as u64);
+        if used.saturating_add(cost) > budget { skipped += paths.len(); continue; }
+        used += cost;
+        picked.extend(paths.into_iter().map(|p| (p, size)));
+    }
+    (picked, skipped)
+}
+
+/// Hex SHA-256 of a file, streamed in 64 KB chunks
+pub fn sha256_file(path: &str) -> io::Result<String> {
+    let mut f = File::open(path)?;
+    let (mut h, mut buf) = (Sha256::new(), vec![0u8; 64 * 1024]);
+    loop {
+        let n = f.read(&mut buf)?;
+        if n == 0 { break; }
+        h.update(&buf[..n]);
+    }
+    Ok(h.finalize().iter().fold(String::with_capacity(64), |mut s, b| { let _ = write!(s, "{:02x}", b); s }))
+}
+
+/// Sets of identical files from hashed (path, size, hash) rows, most reclaimable space first
+pub fn report(q: &str, hashed: Vec<(String, u64, io::Result<String>)>, skipped: usize) -> String {
+    let bytes: u64 = hashed.iter().map(|(_, s, _)| s).sum();
+    let count = hashed.len();
+    let mut errors = Vec::new();
+    let mut sets: HashMap<(u64, String), Vec<String>> = HashMap::new();
+    for (path, size, hash) in hashed {
+        match hash {
+            Ok(h) => sets.entry((size, h)).or_default().push(path),
+            Err(e) => errors.push(format!("{}: {}", path, e)),
+        }
+    }
+    let mut sets: Vec<((u64, String), Vec<String>)> = sets.into_iter().filter(|(_, v)| v.len() > 1).collect();
+    sets.sort_by(|a, b| (b.0 .0 * (b.1.len() as u64 - 1)).cmp(&(a.0 .0 * (a.1.len() as u64 - 1))).then_with(|| a.1.cmp(&b.1)));
+    let reclaim: u64 = sets.iter().map(|((size, _), v)| size * (v.len() as u64 - 1)).sum();
+    let mut out = if sets.is_empty() {
+        format!("No identical files among {} same-size candidate(s) for: {}\n", count, q)
+    } else {
+        format!("{} set(s) of identical files, {} reclaimable (hashed {} files, {}):\n", sets.len(), format_size(reclaim), count, format_size(bytes))
+    };
+    for ((size, hash), mut paths) in sets {
+        paths.sort();
+        let _ = write!(out, "\n{} x {} (sha256 {})\n", paths.len(), format_size(size), &hash[..16]);
+        for p in paths { let _ = writeln!(out, "  {}", p); }
+    }
+    if skipped > 0 { let _ = write!(out, "\nNote: {} same-size file(s) not hashed: over max_hash_mb.\n", skipped); }
+    if !errors.is_empty() {
+        let _ = write!(out, "\n{} file(s) could not be read:\n", errors.len());
+        for e in errors { let _ = writeln!(out, "  {}", e); }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(list: &[(&str, u64)]) -> Vec<(String, u64)> { list.iter().map(|(p, s)| (p.to_string(), *s)).collect() }
+
+    #[test]
+    fn only_shared_nonzero_sizes_are_candidates() {
+        let (picked, skipped) = candidates(files(&[("a", 10), ("b", 10), ("c", 11), ("e1", 0), ("e2", 0)]), u64::MAX);
+        assert_eq!(picked, files(&[("a", 10), ("b", 10)]));
+        assert_eq!(skipped, 0);
+    }
+
+    #[test]
+    fn budget_takes_the_largest_groups_that_fit() {
+        let list = files(&[("s1", 10), ("s2", 10), ("m1", 50), ("m2", 50), ("m3", 50), ("l1", 100), ("l2", 100)]);
+        // 200 for the large pair, then 150 for the mediums is over, then 20 for the small pair fits
+        let (picked, skipped) = candidates(list.clone(), 250);
+        assert_eq!(picked, files(&[("l1", 100), ("l2", 100), ("s1", 10), ("s2", 10)]));
+        assert_eq!(skipped, 3);
+        // A budget exactly the total takes everything
+        assert_eq!(candidates(list.clone(), 370), (candidates(list.clone(), u64::MAX).0, 0));
+        // Sizes near the top of u64 saturate rather than wrap into a small cost
+        let huge = files(&[("h1", u64::MAX / 2), ("h2", u64::MAX / 2), ("h3", u64::MAX / 2), ("s1", 10), ("s2", 10)]);
+        assert_eq!(candidates(huge, u64::MAX - 2), (files(&[("s1", 10), ("s2", 10)]), 3));
+        let (picked, skipped) = candidates(list, 0);
+        assert!(picked.is_empty());
+        assert_eq!(skipped, 7);
+    }
+
+    #[test]
+    fn reports_group_by_hash_within_a_size() {
+        let row = |p: &str, s, h: &str| (p.to_string(), s, Ok(h.repeat(16)));
+        let hashed = vec![
+            row(r"C:\b", 100, "a"), row(r"C:\a", 100, "a"), row(r"C:\c", 100, "b"),
+            row(r"C:\d", 200, "c"), row(r"C:\e", 200, "c"),
+            (r"C:\locked".to_string(), 200, Err(io::Error::new(io::ErrorKind::PermissionDenied, "denied"))),
+        ];
+        let out = report("q", hashed, 2);
+        assert!(out.starts_with("2 set(s) of identical files, 300 B reclaimable (hashed 6 files, 900 B):\n"), "{}", out);
+        // Most reclaimable first, paths sorted
+        let d = out.find(r"  C:\d").unwrap();
+        let a = out.find(r"  C:\a").unwrap();
+        assert!(d < a && a < out.find(r"  C:\b").unwrap() && !out.contains(r"  C:\c"), "{}", out);
+        assert!(out.contains("Note: 2 same-size file(s) not hashed") && out.contains("1 file(s) could not be read:\n  C:\\locked: denied"), "{}", out);
+        assert!(report("q", Vec::new(), 0).starts_with("No identical files among 0 same-size candidate(s) for: q"));
+    }
+
+    #[test]
+    fn files_hash_to_hex_sha256() {
+        let path = std::env::temp_dir().join(format!("identical-test-{}.txt", std::process::id()));
+        std::fs::write(&path, "abc").unwrap();
+        let h = sha256_file(path.to_str().unwrap());
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(h.unwrap(), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+}
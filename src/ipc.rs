@@ -0,0 +1,73 @@
+//! Timed Everything queries over reply-window IPC.
+//!
+//! Everything_QueryW(TRUE) blocks until Everything answers. Instead, post the query with a
+//! message-only window as the reply target and pump that window until the WM_COPYDATA reply
+//! arrives or the deadline passes.
+
+use crate::{EvDll, EverythingError, IsQueryReplyFn, SearchError};
+use std::cell::Cell;
+use std::ptr::{null, null_mut};
+use std::sync::Once;
+use std::time::{Duration, Instant};
+use widestring::{u16cstr, U16CStr};
+use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, PeekMessageW, RegisterClassW, HWND_MESSAGE, MSG,
+    PM_REMOVE, WM_COPYDATA, WNDCLASSW,
+};
+
+const CLASS: &U16CStr = u16cstr!("EverythingMcpReply");
+const REPLY_ID: u32 = 0x4D43_5052;
+
+thread_local! {
+    /// Everything_IsQueryReply while a query on this thread is waiting
+    static CHECK: Cell<Option<IsQueryReplyFn>> = const { Cell::new(None) };
+    static DONE: Cell<bool> = const { Cell::new(false) };
+}
+
+unsafe extern "system" fn wndproc(hwnd: HWND, msg: u32, wp: WPARAM, lp: LPARAM) -> LRESULT {
+    if msg == WM_COPYDATA {
+        if let Some(check) = CHECK.get() {
+            // Copies the results into the DLL's result list when this is our reply
+            if check(msg, wp, lp, REPLY_ID) != 0 { DONE.set(true); return 1; }
+        }
+    }
+    DefWindowProcW(hwnd, msg, wp, lp)
+}
+
+/// Send the configured query and wait at most `timeout_ms` for its results
+pub unsafe fn query(dll: &EvDll, timeout_ms: u32) -> Result<(), SearchError> {
+    static REGISTER: Once = Once::new();
+    let hinst = GetModuleHandleW(null());
+    REGISTER.call_once(|| {
+        let wc = WNDCLASSW { lpfnWndProc: Some(wndproc), hInstance: hinst, lpszClassName: CLASS.as_ptr(), ..Default::default() };
+        RegisterClassW(&wc);
+    });
+    let hwnd = CreateWindowExW(0, CLASS.as_ptr(), null(), 0, 0, 0, 0, 0, HWND_MESSAGE, null_mut(), hinst, null());
+    if hwnd.is_null() { return Err(SearchError::Ipc("could not create reply window".into())); }
+
+    (dll.set_reply_window)(hwnd);
+    (dll.set_reply_id)(REPLY_ID);
+    CHECK.set(Some(*dll.is_query_reply));
+    DONE.set(false);
+
+    let sent = (dll.query)(0) != 0;
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms as u64);
+    let mut msg: MSG = Default::default();
+    while sent && !DONE.get() && Instant::now() < deadline {
+        // Sent messages such as WM_COPYDATA are delivered to wndproc while peeking
+        while PeekMessageW(&mut msg, hwnd, 0, 0, PM_REMOVE) != 0 { DispatchMessageW(&msg); }
+        if !DONE.get() { std::thread::sleep(Duration::from_millis(5)); }
+    }
+
+    // Back to blocking mode for the next caller; a late reply goes to a dead window and is dropped
+    (dll.set_reply_window)(null_mut());
+    (dll.set_reply_id)(0);
+    CHECK.set(None);
+    DestroyWindow(hwnd);
+
+    if !sent { Err(SearchError::QueryFailed(EverythingError::from_code((dll.get_err)()))) }
+    else if DONE.get() { Ok(()) }
+    else { Err(SearchError::Timeout(timeout_ms)) }
+}
@@ -0,0 +1,96 @@
+//! Live checks for everything_search's enrich option: stat each result and flag where the index is out of date
+
+use std::fmt::Write as _;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{format_size, pipeline, rfc3339, Hit};
+
+/// Most results stat'd per search
+pub const LIMIT: usize = 200;
+
+/// Modified times within this many seconds count as equal (FAT stores them in 2 s steps)
+const MTIME_SLACK_S: i64 = 2;
+
+/// What the file system says about a result right now
+#[derive(Serialize)]
+pub struct Live {
+    pub exists: bool,
+    #[serde(skip_serializing_if = "Option::is_none")] pub size: Option<u64>,
+    #[serde(serialize_with = "crate::ser_time", skip_serializing_if = "Option::is_none")] pub modified: Option<DateTime<Utc>>,
+    /// Octal mode on Unix, "readonly" or "writable" on Windows
+    #[serde(skip_serializing_if = "Option::is_none")] pub permissions: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub symlink_target: Option<String>,
+    /// Indexed fields that no longer match the disk ("size", "modified"); empty when the index is current
+    #[serde(skip_serializing_if = "Vec::is_empty")] pub stale: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub error: Option<String>,
+}
+
+fn stat(path: &str, is_dir: bool, size: Option<u64>, modified: Option<String>) -> Live {
+    let mut live = Live { exists: true, size: None, modified: None, permissions: None, symlink_target: None, stale: Vec::new(), error: None };
+    let link = match std::fs::symlink_metadata(path) {
+        Ok(m) => m,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => { live.exists = false; return live; }
+        Err(e) => { live.error = Some(e.to_string()); return live; }
+    };
+    if link.file_type().is_symlink() {
+        live.symlink_target = std::fs::read_link(path).ok().map(|t| t.to_string_lossy().into_owned());
+    }
+    // Follow a link for the size and time of what it points at; a dangling one keeps the link's own
+    let m = std::fs::metadata(path).unwrap_or(link);
+    live.modified = m.modified().ok().map(DateTime::<Utc>::from);
+    #[cfg(unix)]
+    { use std::os::unix::fs::PermissionsExt; live.permissions = Some(format!("{:o}", m.permissions().mode() & 0o7777)); }
+    #[cfg(not(unix))]
+    { live.permissions = Some(if m.permissions().readonly() { "readonly" } else { "writable" }.into()); }
+    // Folder sizes and times shift with their contents, so only files are compared
+    if !is_dir && m.is_file() {
+        live.size = Some(m.len());
+        if size.is_some_and(|s| s != m.len()) { live.stale.push("size"); }
+        let indexed = modified.and_then(|t| DateTime::parse_from_rfc3339(&t).ok()).map(|t| t.with_timezone(&Utc));
+        if let (Some(a), Some(b)) = (indexed, live.modified) {
+            if (a - b).num_seconds().abs() > MTIME_SLACK_S { live.stale.push("modified"); }
+        }
+    }
+    live
+}
+
+/// Stat the first `limit` hits in parallel and attach the results; returns how many were checked
+pub async fn check(hits: &mut [Hit], limit: usize) -> usize {
+    let items: Vec<_> = hits.iter().take(limit).enumerate()
+        .map(|(i, h)| (i, h.path.clone(), h.is_dir, h.size, h.date_modified.clone())).collect();
+    let n = items.len();
+    let lives = pipeline::enrich(items, *pipeline::WORKERS, |(i, path, is_dir, size, modified)| (i, stat(&path, is_dir, size, modified))).await;
+    for (i, live) in lives { hits[i].live = Some(live); }
+    n
+}
+
+/// Text summary of the checked hits: counts, then one line per missing, changed or linked file
+pub fn report(hits: &[Hit], checked: usize) -> String {
+    let lives = || hits.iter().filter_map(|h| h.live.as_ref().map(|l| (h, l)));
+    let missing = lives().filter(|(_, l)| !l.exists).count();
+    let changed = lives().filter(|(_, l)| !l.stale.is_empty()).count();
+    let mut out = format!("\nLive check of {} result(s): {} missing, {} changed since indexing", checked, missing, changed);
+    if hits.len() > checked { let _ = write!(out, " (only the first {} are checked)", checked); }
+    out.push('\n');
+    let time = |t: Option<DateTime<Utc>>| t.as_ref().map_or_else(|| "-".into(), rfc3339);
+    for (h, l) in lives() {
+        let _ = if !l.exists {
+            writeln!(out, "  missing: {}", h.path)
+        } else if let Some(e) = &l.error {
+            writeln!(out, "  unreadable ({}): {}", e, h.path)
+        } else if !l.stale.is_empty() {
+            let mut what = Vec::new();
+            if l.stale.contains(&"size") {
+                what.push(format!("size {} -> {}", h.size.map_or_else(|| "-".into(), format_size), l.size.map_or_else(|| "-".into(), format_size)));
+            }
+            if l.stale.contains(&"modified") { what.push(format!("modified {} -> {}", h.date_modified.as_deref().unwrap_or("-"), time(l.modified))); }
+            writeln!(out, "  changed ({}): {}", what.join("; "), h.path)
+        } else {
+            Ok(())
+        };
+        if let Some(t) = &l.symlink_target { let _ = writeln!(out, "  symlink: {} -> {}", h.path, t); }
+    }
+    out
+}
@@ -1,29 +1,72 @@
 //! Everything MCP Server in Rust - with CLI mode
 
-use clap::{Parser, Subcommand};
+mod api;
+mod backend;
+mod cache;
+mod categories;
+mod collect;
+mod doctor;
+mod explain;
+mod filters;
+mod glob;
+mod identical;
+#[cfg(windows)]
+mod ipc;
+mod live;
+mod mojibake;
+mod pathbuf;
+mod pipeline;
+mod query;
+mod rename;
+mod saved;
+mod snippet;
+mod template;
+mod worker;
+
+use clap::{CommandFactory, Parser, Subcommand};
 use rmcp::{
     ServerHandler, ServiceExt,
-    model::{ServerInfo, ServerCapabilities, Implementation, ProtocolVersion, CallToolResult, Content},
-    tool, tool_router, tool_handler,
-    handler::server::{router::tool::ToolRouter, wrapper::Parameters},
+    model::{
+        ServerInfo, ServerCapabilities, Implementation, ProtocolVersion, CallToolResult, Content, AnnotateAble,
+        CallToolRequestParam, GetPromptRequestParam, GetPromptResult, ListPromptsResult, Prompt, PromptArgument, PromptMessage, PromptMessageRole,
+        ListResourcesResult, ListToolsResult, PaginatedRequestParam, RawResource, ReadResourceRequestParam, ReadResourceResult, ResourceContents,
+    },
+    service::RequestContext,
+    tool, tool_router, RoleServer,
+    handler::server::{router::tool::ToolRouter, tool::ToolCallContext, wrapper::Parameters},
     transport::stdio,
     ErrorData as McpError,
 };
 use libloading::{Library, Symbol};
 use schemars::JsonSchema;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use widestring::U16CString;
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Mutex;
+use api::EverythingApi;
+use query::QueryBuilder;
+use tracing::Instrument;
 
 type SetSearchFn = unsafe extern "system" fn(*const u16);
 type SetU32Fn = unsafe extern "system" fn(u32);
 type SetI32Fn = unsafe extern "system" fn(i32);
 type QueryFn = unsafe extern "system" fn(i32) -> i32;
 type GetU32Fn = unsafe extern "system" fn() -> u32;
-type GetPathFn = unsafe extern "system" fn(u32, *mut u16, u32);
+type GetPathFn = unsafe extern "system" fn(u32, *mut u16, u32) -> u32;
 type GetAttrFn = unsafe extern "system" fn(u32) -> u32;
+type GetStrFn = unsafe extern "system" fn(u32) -> *const u16;
 type IsLoadedFn = unsafe extern "system" fn() -> i32;
+type GetSizeFn = unsafe extern "system" fn(u32, *mut i64) -> i32;
+type GetDateFn = unsafe extern "system" fn(u32, *mut u64) -> i32;
+type SetHwndFn = unsafe extern "system" fn(*mut std::ffi::c_void);
+type IsFastSortFn = unsafe extern "system" fn(u32) -> i32;
+type RunCountFn = unsafe extern "system" fn(*const u16) -> u32;
+type SetRunCountFn = unsafe extern "system" fn(*const u16, u32) -> i32;
+type IsQueryReplyFn = unsafe extern "system" fn(u32, usize, isize, u32) -> i32;
 
 struct EvDll {
     set_search: Symbol<'static, SetSearchFn>,
@@ -33,22 +76,64 @@ struct EvDll {
     set_regex: Symbol<'static, SetI32Fn>,
     set_path: Symbol<'static, SetI32Fn>,
     set_flags: Symbol<'static, SetU32Fn>,
+    set_sort: Symbol<'static, SetU32Fn>,
+    set_offset: Symbol<'static, SetU32Fn>,
     query: Symbol<'static, QueryFn>,
     get_num: Symbol<'static, GetU32Fn>,
     get_tot: Symbol<'static, GetU32Fn>,
     get_path: Symbol<'static, GetPathFn>,
     get_attr: Symbol<'static, GetAttrFn>,
+    get_file_name: Symbol<'static, GetStrFn>,
+    get_ext: Symbol<'static, GetStrFn>,
+    get_highlighted: Symbol<'static, GetStrFn>,
+    get_size: Symbol<'static, GetSizeFn>,
+    get_date_modified: Symbol<'static, GetDateFn>,
+    get_date_created: Symbol<'static, GetDateFn>,
+    get_date_accessed: Symbol<'static, GetDateFn>,
+    get_run_count: Symbol<'static, GetAttrFn>,
+    get_file_list: Symbol<'static, GetStrFn>,
+    // Reply-window IPC, only driven on Windows (see ipc.rs)
+    #[cfg_attr(not(windows), allow(dead_code))]
+    set_reply_window: Symbol<'static, SetHwndFn>,
+    #[cfg_attr(not(windows), allow(dead_code))]
+    set_reply_id: Symbol<'static, SetU32Fn>,
+    #[cfg_attr(not(windows), allow(dead_code))]
+    is_query_reply: Symbol<'static, IsQueryReplyFn>,
     get_err: Symbol<'static, GetU32Fn>,
     is_loaded: Symbol<'static, IsLoadedFn>,
     get_ver: [Symbol<'static, GetU32Fn>; 4],
+    /// Missing from SDK builds before 1.4.1
+    is_fast_sort: Option<Symbol<'static, IsFastSortFn>>,
+    /// Run count by path; also missing before 1.4.1
+    inc_run_count: Option<Symbol<'static, RunCountFn>>,
+    get_run_count_by_name: Option<Symbol<'static, RunCountFn>>,
+    set_run_count: Option<Symbol<'static, SetRunCountFn>>,
+    /// Path it was loaded from, as given (a bare name was found on the DLL search path)
+    path: String,
 }
 
 impl EvDll {
+    /// --dll-path, then EVERYTHING_DLL_PATH, then Everything64.dll on the search path, then the default install location
+    #[tracing::instrument(err)]
     fn load() -> Result<Self, String> {
+        Self::load_chosen(DLL_PATH.get().cloned(), std::env::var("EVERYTHING_DLL_PATH").ok())
+    }
+
+    /// load() with the --dll-path and EVERYTHING_DLL_PATH values given
+    fn load_chosen(flag: Option<String>, env: Option<String>) -> Result<Self, String> {
+        let defaults = || Self::load_from("Everything64.dll").or_else(|_| Self::load_from("C:\\Program Files\\Everything\\Everything64.dll"));
+        let chosen = flag.map(|p| ("--dll-path", p))
+            .or_else(|| env.filter(|p| !p.is_empty()).map(|p| ("EVERYTHING_DLL_PATH", p)));
+        match chosen {
+            Some((src, p)) => Self::load_from(&p).or_else(|e| defaults().map_err(|_| format!("{}={}: {}", src, p, e))),
+            None => defaults(),
+        }
+    }
+
+    fn load_from(path: &str) -> Result<Self, String> {
+        tracing::debug!(path, "loading Everything DLL");
         unsafe {
-            let lib = Library::new("Everything64.dll")
-                .or_else(|_| Library::new("C:\\Program Files\\Everything\\Everything64.dll"))
-                .map_err(|e| e.to_string())?;
+            let lib = Library::new(path).map_err(|e| e.to_string())?;
             let lib: &'static Library = Box::leak(Box::new(lib));
             
             Ok(Self {
@@ -59,11 +144,25 @@ impl EvDll {
                 set_regex: lib.get(b"Everything_SetRegex\0").map_err(|e| e.to_string())?,
                 set_path: lib.get(b"Everything_SetMatchPath\0").map_err(|e| e.to_string())?,
                 set_flags: lib.get(b"Everything_SetRequestFlags\0").map_err(|e| e.to_string())?,
+                set_sort: lib.get(b"Everything_SetSort\0").map_err(|e| e.to_string())?,
+                set_offset: lib.get(b"Everything_SetOffset\0").map_err(|e| e.to_string())?,
                 query: lib.get(b"Everything_QueryW\0").map_err(|e| e.to_string())?,
                 get_num: lib.get(b"Everything_GetNumResults\0").map_err(|e| e.to_string())?,
                 get_tot: lib.get(b"Everything_GetTotResults\0").map_err(|e| e.to_string())?,
                 get_path: lib.get(b"Everything_GetResultFullPathNameW\0").map_err(|e| e.to_string())?,
                 get_attr: lib.get(b"Everything_GetResultAttributes\0").map_err(|e| e.to_string())?,
+                get_file_name: lib.get(b"Everything_GetResultFileNameW\0").map_err(|e| e.to_string())?,
+                get_ext: lib.get(b"Everything_GetResultExtensionW\0").map_err(|e| e.to_string())?,
+                get_highlighted: lib.get(b"Everything_GetResultHighlightedFileNameW\0").map_err(|e| e.to_string())?,
+                get_size: lib.get(b"Everything_GetResultSize\0").map_err(|e| e.to_string())?,
+                get_date_modified: lib.get(b"Everything_GetResultDateModified\0").map_err(|e| e.to_string())?,
+                get_date_created: lib.get(b"Everything_GetResultDateCreated\0").map_err(|e| e.to_string())?,
+                get_date_accessed: lib.get(b"Everything_GetResultDateAccessed\0").map_err(|e| e.to_string())?,
+                get_run_count: lib.get(b"Everything_GetResultRunCount\0").map_err(|e| e.to_string())?,
+                get_file_list: lib.get(b"Everything_GetResultFileListFileNameW\0").map_err(|e| e.to_string())?,
+                set_reply_window: lib.get(b"Everything_SetReplyWindow\0").map_err(|e| e.to_string())?,
+                set_reply_id: lib.get(b"Everything_SetReplyID\0").map_err(|e| e.to_string())?,
+                is_query_reply: lib.get(b"Everything_IsQueryReply\0").map_err(|e| e.to_string())?,
                 get_err: lib.get(b"Everything_GetLastError\0").map_err(|e| e.to_string())?,
                 is_loaded: lib.get(b"Everything_IsDBLoaded\0").map_err(|e| e.to_string())?,
                 get_ver: [
@@ -72,272 +171,2117 @@ impl EvDll {
                     lib.get(b"Everything_GetRevision\0").map_err(|e| e.to_string())?,
                     lib.get(b"Everything_GetBuildNumber\0").map_err(|e| e.to_string())?,
                 ],
+                is_fast_sort: lib.get(b"Everything_IsFastSort\0").ok(),
+                inc_run_count: lib.get(b"Everything_IncRunCountFromFileNameW\0").ok(),
+                get_run_count_by_name: lib.get(b"Everything_GetRunCountFromFileNameW\0").ok(),
+                set_run_count: lib.get(b"Everything_SetRunCountFromFileNameW\0").ok(),
+                path: path.to_string(),
             })
         }
     }
 }
 
-static DLL: Lazy<Mutex<Option<EvDll>>> = Lazy::new(|| Mutex::new(EvDll::load().ok()));
+/// DLL location from --dll-path; must be set before DLL is first touched
+static DLL_PATH: OnceCell<String> = OnceCell::new();
+
+/// TTL from --cache-ttl; falls back to EVERYTHING_CACHE_TTL, then 5 seconds
+static CACHE_TTL: OnceCell<u64> = OnceCell::new();
+
+fn cache_ttl() -> std::time::Duration {
+    let secs = CACHE_TTL.get().copied()
+        .or_else(|| std::env::var("EVERYTHING_CACHE_TTL").ok().and_then(|v| v.parse().ok()))
+        .unwrap_or(5);
+    std::time::Duration::from_secs(secs)
+}
+
+/// Ceiling from --max-cap; see max_cap()
+static MAX_CAP: OnceCell<u32> = OnceCell::new();
+
+/// Most results any one search returns: --max-cap, else EVERYTHING_MAX_RESULTS, else 500 (which is also the upper limit)
+fn max_cap() -> u32 {
+    MAX_CAP.get().copied()
+        .or_else(|| std::env::var("EVERYTHING_MAX_RESULTS").ok().and_then(|v| v.parse().ok()))
+        .unwrap_or(500).clamp(1, 500)
+}
+
+/// Server-wide result line template from --template; see format_hits
+static TEMPLATE: OnceCell<template::Template> = OnceCell::new();
+
+/// --quiet: result lines only, without the "Found N" header or notes
+static QUIET: OnceCell<bool> = OnceCell::new();
+
+fn quiet() -> bool { QUIET.get().copied().unwrap_or(false) }
+
+/// --si: sizes in 1000-based units; see si_sizes()
+static SI_SIZES: OnceCell<bool> = OnceCell::new();
+
+/// --si, else EVERYTHING_SI_SIZES=1
+fn si_sizes() -> bool {
+    SI_SIZES.get().copied().unwrap_or(false) || std::env::var("EVERYTHING_SI_SIZES").is_ok_and(|v| v == "1")
+}
+
+/// Fallback page size from --default-max; see default_max()
+static DEFAULT_MAX: OnceCell<u32> = OnceCell::new();
+
+/// Results returned when a request omits max_results: --default-max, else EVERYTHING_DEFAULT_MAX, else the tool's
+/// own `builtin` (50 for most tools, 20 for content search, 100 or 500 for tools that post-process a working set)
+fn default_max(builtin: u32) -> u32 {
+    DEFAULT_MAX.get().copied()
+        .or_else(|| std::env::var("EVERYTHING_DEFAULT_MAX").ok().and_then(|v| v.parse().ok()))
+        .map_or(builtin, |n: u32| n.clamp(1, 500))
+}
+
+/// Retry count from --ipc-retries; see ipc_retries()
+static IPC_RETRIES: OnceCell<u32> = OnceCell::new();
+
+/// Extra attempts when a query fails with EVERYTHING_ERROR_IPC, as it does while Everything restarts:
+/// --ipc-retries, else EVERYTHING_IPC_RETRIES, else 3 (at most 10)
+fn ipc_retries() -> u32 {
+    IPC_RETRIES.get().copied()
+        .or_else(|| std::env::var("EVERYTHING_IPC_RETRIES").ok().and_then(|v| v.parse().ok()))
+        .unwrap_or(3).min(10)
+}
+
+/// Line to append when max_cap() cut a page short of what the request asked for
+fn cap_note(max: u32, total: u32) -> String {
+    let cap = max_cap();
+    if !quiet() && max > cap && total > cap { format!("\n(capped at {} results per call)", cap) } else { String::new() }
+}
+
+/// Query, settings and output format of an everything_search call
+type CacheKey = (String, Opts, String);
+
+/// Offset of a result page and its paths
+type ResultSet = (u32, Vec<String>);
+
+/// everything_search output, with the query_id it contains and that id's result set
+type Cached = (String, Option<(String, ResultSet)>);
+
+/// Recent everything_search output. A query_id in it is registered again whenever it is served from here, so the
+/// id stays valid for a minute from then
+static CACHE: Lazy<Mutex<cache::TtlCache<CacheKey, Cached>>> = Lazy::new(|| Mutex::new(cache::TtlCache::new(64)));
+
+/// Paths of recent JSON result pages by query_id, so follow-up tools can take an index instead of a path
+static RESULT_SETS: Lazy<Mutex<cache::TtlCache<String, ResultSet>>> = Lazy::new(|| Mutex::new(cache::TtlCache::new(32)));
+static NEXT_QUERY_ID: AtomicU32 = AtomicU32::new(1);
+const RESULT_SET_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Keep a page's paths (the first at `offset`) for a minute and return its query_id
+fn remember_results(offset: u32, hits: &[Hit]) -> String {
+    let id = format!("q{}", NEXT_QUERY_ID.fetch_add(1, Ordering::Relaxed));
+    if let Ok(mut c) = RESULT_SETS.lock() { c.put(id.clone(), (offset, hits.iter().map(|h| h.path.clone()).collect())); }
+    id
+}
+
+/// Path a follow-up tool should act on: `path`, or result `index` of an earlier `query_id`
+fn resolve_path(p: &PathReq) -> Result<String, McpError> {
+    match (&p.path, &p.query_id, p.index) {
+        (Some(path), _, _) => Ok(path.clone()),
+        (None, Some(id), Some(i)) => {
+            let set = RESULT_SETS.lock().ok().and_then(|mut c| c.get(id, RESULT_SET_TTL))
+                .ok_or_else(|| McpError::invalid_params(format!("Unknown or expired query_id '{}' (results are kept for a minute)", id), None))?;
+            i.checked_sub(set.0).and_then(|k| set.1.get(k as usize)).cloned()
+                .ok_or_else(|| McpError::invalid_params(format!("index {} is not in {} (it has {}..{})", i, id, set.0, set.0 + set.1.len() as u32), None))
+        }
+        _ => Err(McpError::invalid_params("give path, or query_id and index from an everything_search JSON result", None)),
+    }
+}
+
+/// Load the DLL again in place of the current one, e.g. after a failed load at startup. The old library is
+/// leaked like every load, so symbols held elsewhere stay valid
+async fn reload_dll() -> Result<String, String> {
+    if BACKEND.name() != "Everything" { return Err(format!("Nothing to reload: using the {} fallback", BACKEND.name())); }
+    worker::call(|g| {
+        *g = EvDll::load();
+        let dll = g.as_ref().map_err(|e| format!("Reload failed: {}", e))?;
+        unsafe {
+            if (dll.is_loaded)() != 0 {
+                Ok(format!("Reloaded: Everything v{}.{}.{}.{} Ready", (dll.get_ver[0])(), (dll.get_ver[1])(), (dll.get_ver[2])(), (dll.get_ver[3])()))
+            } else {
+                Ok("Reloaded the DLL, but Everything is not available. Is it running?".into())
+            }
+        }
+    }).await.map_err(|e| e.to_string())?
+}
+
+/// --allow-writes; see writes_allowed()
+static ALLOW_WRITES: OnceCell<bool> = OnceCell::new();
+
+/// Tools that write to disk or change Everything's data stay disabled unless --allow-writes or EVERYTHING_ALLOW_WRITES=1
+fn writes_allowed() -> Result<(), McpError> {
+    if ALLOW_WRITES.get().copied().unwrap_or(false) || std::env::var("EVERYTHING_ALLOW_WRITES").is_ok_and(|v| v == "1") { return Ok(()); }
+    Err(McpError::invalid_request("Writes disabled. Pass --allow-writes or set EVERYTHING_ALLOW_WRITES=1 to enable.", None))
+}
+
+/// Where a sync token stands: the second its previous call started, and the (path, date modified) pairs that call
+/// returned from that second on, which the next call's `dm:>=` matches again
+#[derive(Clone)]
+struct SyncMark { since: chrono::DateTime<chrono::Utc>, seen: std::collections::HashSet<(String, Option<String>)> }
+
+/// Incremental sync state by sync token. Held in memory only, so tokens reset when the server restarts.
+static SYNC: Lazy<Mutex<HashMap<String, SyncMark>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// EVERYTHING_REQUEST_* flags
+const REQUEST_FILE_NAME: u32 = 0x1;
+const REQUEST_PATH: u32 = 0x2;
+const REQUEST_EXTENSION: u32 = 0x8;
+const REQUEST_SIZE: u32 = 0x10;
+const REQUEST_DATE_CREATED: u32 = 0x20;
+const REQUEST_DATE_MODIFIED: u32 = 0x40;
+const REQUEST_DATE_ACCESSED: u32 = 0x80;
+const REQUEST_ATTRIBUTES: u32 = 0x100;
+const REQUEST_FILE_LIST_FILE_NAME: u32 = 0x200;
+const REQUEST_RUN_COUNT: u32 = 0x400;
+const REQUEST_HIGHLIGHTED_FILE_NAME: u32 = 0x2000;
+
+const SORT_SIZE_DESCENDING: u32 = 6;
+const SORT_RUN_COUNT_DESCENDING: u32 = 20;
+const SORT_DATE_ACCESSED_ASCENDING: u32 = 23;
+
+/// EVERYTHING_SORT_*_ASCENDING for a sort key; the descending constant is always one higher
+fn sort_code(key: &str, ascending: bool) -> Result<u32, String> {
+    let asc = match key {
+        "name" => 1,
+        "path" => 3,
+        "size" => 5,
+        "extension" => 7,
+        "date_created" => 11,
+        "date_modified" => 13,
+        "run_count" => 19,
+        "date_accessed" => 23,
+        _ => return Err(format!("Unknown sort_by '{}' (expected name, path, size, extension, date_modified, date_created, date_accessed, run_count)", key)),
+    };
+    Ok(if ascending { asc } else { asc + 1 })
+}
+
+/// Whether Everything has an index for `sort`; None when unknown (fallback backend, or a DLL without IsFastSort)
+async fn fast_sort(sort: u32) -> Option<bool> {
+    if BACKEND.name() != "Everything" { return None; }
+    worker::call(move |g| {
+        let f = g.as_ref().ok()?.is_fast_sort.as_ref()?;
+        Some(unsafe { f(sort) } != 0)
+    }).await.ok().flatten()
+}
+
+/// Run `f` with the loaded DLL and `path` as a wide string, for Everything's by-file-name run count functions
+async fn with_run_count<T: Send + 'static>(path: &str, f: impl FnOnce(&EvDll, *const u16) -> Result<T, SearchError> + Send + 'static) -> Result<T, SearchError> {
+    if BACKEND.name() != "Everything" { return Err(SearchError::Backend(format!("run counts need Everything (backend: {})", BACKEND.name()))); }
+    let path = path.to_string();
+    worker::call(move |g| {
+        let dll = g.as_ref().map_err(|e| SearchError::NotLoaded(e.clone()))?;
+        let w = U16CString::from_str(&path).map_err(|e| SearchError::BadQuery(e.to_string()))?;
+        f(dll, w.as_ptr())
+    }).await?
+}
+
+/// One of the optional run count symbols, or why it is missing
+fn run_count_fn<T>(s: &Option<T>) -> Result<&T, SearchError> {
+    s.as_ref().ok_or_else(|| SearchError::Backend("this Everything64.dll has no run count functions (SDK 1.4.1 or later needed)".into()))
+}
+
+/// A file's run count in Everything's database (0 for files never run or not indexed)
+async fn get_run_count(path: &str) -> Result<u32, SearchError> {
+    with_run_count(path, |dll, w| Ok(unsafe { run_count_fn(&dll.get_run_count_by_name)?(w) })).await
+}
+
+/// Add one to a file's run count, returning the new count
+async fn inc_run_count(path: &str) -> Result<u32, SearchError> {
+    with_run_count(path, |dll, w| unsafe {
+        // IncRunCount returns the new count, or 0 when it fails (e.g. the file isn't indexed)
+        if run_count_fn(&dll.inc_run_count)?(w) == 0 { return Err(SearchError::QueryFailed(EverythingError::from_code((dll.get_err)()))); }
+        Ok(run_count_fn(&dll.get_run_count_by_name)?(w))
+    }).await
+}
+
+/// Overwrite a file's run count, returning (before, after)
+async fn set_run_count(path: &str, n: u32) -> Result<(u32, u32), SearchError> {
+    with_run_count(path, move |dll, w| unsafe {
+        let get = run_count_fn(&dll.get_run_count_by_name)?;
+        let before = get(w);
+        if run_count_fn(&dll.set_run_count)?(w, n) == 0 { return Err(SearchError::QueryFailed(EverythingError::from_code((dll.get_err)()))); }
+        Ok((before, get(w)))
+    }).await
+}
+
+/// Largest EVERYTHING_PATH_BUF accepted (the \\?\ path limit); longer paths still grow the buffer on demand
+const MAX_PATH_BUF: usize = 32768;
+
+/// Starting size of the per-thread path buffer; override with EVERYTHING_PATH_BUF (UTF-16 units)
+static PATH_BUF_START: Lazy<usize> = Lazy::new(|| {
+    std::env::var("EVERYTHING_PATH_BUF").ok().and_then(|v| v.parse().ok()).unwrap_or(1024).clamp(260, MAX_PATH_BUF)
+});
+
+thread_local! {
+    /// Reused across searches so small queries don't pay for a 64KB allocation each call; benches/path_buf.rs
+    /// measures the difference
+    static PATH_BUF: RefCell<Vec<u16>> = RefCell::new(vec![0u16; *PATH_BUF_START]);
+}
+
+/// Full path of result i, growing the thread-local buffer only when a path doesn't fit
+fn result_path(api: &dyn EverythingApi, i: u32) -> String {
+    PATH_BUF.with(|b| {
+        let mut buf = b.borrow_mut();
+        let (n, need) = pathbuf::read_into(&mut buf, |s| api.full_path(i, s));
+        if n < need { tracing::warn!(index = i, len = n, need, "result path truncated"); }
+        String::from_utf16_lossy(&buf[..n])
+    })
+}
+
+/// FILETIME (100ns ticks since 1601-01-01 UTC) as RFC 3339 UTC text to the second; 0 and u64::MAX mean unknown.
+/// Every date read from Everything goes through here
+fn filetime_to_rfc3339(ft: u64) -> Option<String> {
+    const EPOCH_DIFF_SECS: i64 = 11_644_473_600;
+    if ft == 0 || ft == u64::MAX { return None; }
+    let secs = (ft / 10_000_000) as i64 - EPOCH_DIFF_SECS;
+    chrono::DateTime::from_timestamp(secs, 0).as_ref().map(rfc3339)
+}
+
+/// "2024-01-31T12:00:00Z"; the one format dates are kept and output in. Fixed width, so these compare as text
+fn rfc3339(t: &chrono::DateTime<chrono::Utc>) -> String {
+    t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// "2024-01-31 12:00" for text listings
+fn short_time(t: &str) -> String {
+    t.get(..16).map_or_else(|| t.to_string(), |s| s.replacen('T', " ", 1))
+}
+
+fn ser_time<S: serde::Serializer>(t: &Option<chrono::DateTime<chrono::Utc>>, s: S) -> Result<S::Ok, S::Error> {
+    match t {
+        Some(t) => s.serialize_str(&rfc3339(t)),
+        None => s.serialize_none(),
+    }
+}
+
+/// --categories, then EVERYTHING_CATEGORIES; unset means the built-in lists
+static CATEGORIES_PATH: OnceCell<String> = OnceCell::new();
+
+/// Category name -> extensions, checked once at startup so a bad config file fails loudly
+static CATEGORIES: Lazy<Result<HashMap<String, Vec<String>>, String>> = Lazy::new(|| {
+    let path = CATEGORIES_PATH.get().cloned().or_else(|| std::env::var("EVERYTHING_CATEGORIES").ok());
+    categories::load(path.as_deref())
+});
+
+/// Saved-search file from --saved-searches; see saved_path()
+static SAVED_PATH: OnceCell<String> = OnceCell::new();
+
+/// --saved-searches, else EVERYTHING_SAVED_SEARCHES, else saved-searches.json in the user's config folder
+fn saved_path() -> Result<std::path::PathBuf, McpError> {
+    SAVED_PATH.get().cloned()
+        .or_else(|| std::env::var("EVERYTHING_SAVED_SEARCHES").ok().filter(|p| !p.is_empty()))
+        .map(Into::into).or_else(saved::default_path)
+        .ok_or_else(|| McpError::internal_error("No config folder found; set EVERYTHING_SAVED_SEARCHES or --saved-searches", None))
+}
+
+const QUERY_PROMPT_NAME: &str = "build_query";
+
+/// Body of the build_query prompt; {goal}, {folder}, {types}, {age} and {size} are filled from its arguments
+const QUERY_PROMPT: &str = "\
+Help me write an Everything search query, then run it with everything_search.
+
+What I'm looking for: {goal}
+Folder: {folder}
+File types: {types}
+Modified: {age}
+Size: {size}
+
+Everything query syntax:
+- Terms separated by spaces must all match (AND); a|b matches either; !term excludes; <a | b> groups.
+  Parentheses are literal characters, not grouping.
+- Quote terms with spaces: \"annual report\"; a quote inside quotes is doubled: \"say \"\"hi\"\"\".
+  Wildcards: * and ?. A term matches anywhere in the name unless path: is on or the term contains a backslash.
+- \"C:\\Projects\\\" limits results to that folder and everything below it.
+- ext:pdf;docx - extensions, separated by semicolons.
+- dm:today, dm:last7days, dm:>2024-01-01, dm:2024-01..2024-06 - date modified (dc: created, da: accessed).
+- size:>100mb, size:1mb..10mb, size:huge - file size.
+- file: / folder: - only files or only folders.
+- dupe: - files sharing a name with another result; sizedupe: for same size.
+- regex:^IMG_\\d{4}\\.jpg$ - a regular expression on the name (or pass regex: true to everything_search).
+- content:\"text\" - searches inside files; slow, so narrow it with ext: and a folder first.
+
+Leave out anything marked \"not specified\". Show the query and say in one line what each part does,
+check it with everything_explain or dry_run: true if unsure, then run it.";
+
+/// QUERY_PROMPT with each slot replaced by its argument, or \"not specified\"
+fn query_prompt(args: Option<&serde_json::Map<String, serde_json::Value>>) -> String {
+    ["goal", "folder", "types", "age", "size"].iter().fold(QUERY_PROMPT.to_string(), |text, slot| {
+        let v = args.and_then(|a| a.get(*slot)).and_then(|v| v.as_str()).map(str::trim).filter(|v| !v.is_empty());
+        text.replace(&format!("{{{}}}", slot), v.unwrap_or("not specified"))
+    })
+}
+
+const CATEGORIES_URI: &str = "everything://categories";
+
+/// "ext:mp3;wav;..." for a CATEGORIES entry
+fn category_exts(name: &str) -> String {
+    CATEGORIES.as_ref().ok().and_then(|m| m.get(name)).map(|e| e.join(";")).unwrap_or_default()
+}
+
+/// `dm:>cutoff` / `dm:<cutoff` (or dc:) for the age tools, with the cutoff `duration` before now in local time
+fn age_query(p: &AgeReq, op: &str) -> Result<String, McpError> {
+    let cutoff = filters::duration_before(&p.duration, chrono::Local::now().naive_local()).map_err(|e| McpError::invalid_params(e, None))?;
+    let date = format!("{}{}", op, cutoff.format("%Y-%m-%dT%H:%M:%S"));
+    let q = match p.which.as_deref().map(str::to_lowercase).as_deref() {
+        None | Some("modified") => QueryBuilder::new().modified(&date),
+        Some("created") => QueryBuilder::new().created(&date),
+        Some(w) => return Err(McpError::invalid_params(format!("Unknown which '{}' (expected \"modified\" or \"created\")", w), None)),
+    };
+    build(q.ext(p.extension.as_deref().unwrap_or("")).keywords(p.keywords.as_deref()))
+}
+
+/// Finish a QueryBuilder, reporting its first bad value as invalid params
+fn build(q: QueryBuilder) -> Result<String, McpError> {
+    q.build().map_err(|e| McpError::invalid_params(e, None))
+}
+
+/// EVERYTHING_ERROR_* codes from Everything_GetLastError
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EverythingError {
+    Memory,
+    Ipc,
+    RegisterClassEx,
+    CreateWindow,
+    CreateThread,
+    InvalidIndex,
+    InvalidCall,
+    InvalidRequest,
+    InvalidParameter,
+    Unknown(u32),
+}
+
+impl EverythingError {
+    /// Code, symbolic name and meaning of every documented error
+    const TABLE: &'static [(u32, EverythingError, &'static str, &'static str)] = &[
+        (1, Self::Memory, "EVERYTHING_ERROR_MEMORY", "out of memory"),
+        (2, Self::Ipc, "EVERYTHING_ERROR_IPC", "Everything search client is not running"),
+        (3, Self::RegisterClassEx, "EVERYTHING_ERROR_REGISTERCLASSEX", "unable to register window class"),
+        (4, Self::CreateWindow, "EVERYTHING_ERROR_CREATEWINDOW", "unable to create listening window"),
+        (5, Self::CreateThread, "EVERYTHING_ERROR_CREATETHREAD", "unable to create listening thread"),
+        (6, Self::InvalidIndex, "EVERYTHING_ERROR_INVALIDINDEX", "invalid result index"),
+        (7, Self::InvalidCall, "EVERYTHING_ERROR_INVALIDCALL", "invalid call"),
+        (8, Self::InvalidRequest, "EVERYTHING_ERROR_INVALIDREQUEST", "invalid request data, request data first"),
+        (9, Self::InvalidParameter, "EVERYTHING_ERROR_INVALIDPARAMETER", "bad parameter"),
+    ];
+
+    fn from_code(code: u32) -> Self {
+        Self::TABLE.iter().find(|t| t.0 == code).map(|t| t.1).unwrap_or(Self::Unknown(code))
+    }
+
+    fn code(self) -> u32 {
+        match self { Self::Unknown(c) => c, e => Self::TABLE.iter().find(|t| t.1 == e).map_or(0, |t| t.0) }
+    }
+
+    fn name(self) -> &'static str {
+        Self::TABLE.iter().find(|t| t.1 == self).map_or("EVERYTHING_ERROR_UNKNOWN", |t| t.2)
+    }
+}
+
+impl std::fmt::Display for EverythingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let what = Self::TABLE.iter().find(|t| t.1 == *self).map_or("unknown error", |t| t.3);
+        write!(f, "{} ({}): {}", self.name(), self.code(), what)
+    }
+}
 
-fn search(q: &str, max: u32, case: bool, word: bool, regex: bool, path: bool) -> String {
-    let guard = match DLL.lock() { Ok(g) => g, Err(e) => return format!("Lock: {}", e) };
-    let dll = match guard.as_ref() { Some(d) => d, None => return "DLL not loaded".into() };
-    
-    unsafe {
-        let qw = match U16CString::from_str(q) { Ok(s) => s, Err(e) => return format!("Query: {}", e) };
-        (dll.set_search)(qw.as_ptr());
-        (dll.set_max)(max.clamp(1, 500));
-        (dll.set_case)(case as i32);
-        (dll.set_word)(word as i32);
-        (dll.set_regex)(regex as i32);
-        (dll.set_path)(path as i32);
-        (dll.set_flags)(0x113);
-        
-        if (dll.query)(1) == 0 { return format!("Query failed ({}). Is Everything running?", (dll.get_err)()); }
-        
-        let n = (dll.get_num)();
-        if n == 0 { return format!("No results for: {}", q); }
-        
-        let mut out = format!("Found {} (showing {}):\n\n", (dll.get_tot)(), n);
-        let mut buf = vec![0u16; 32768];
-        
-        for i in 0..n {
-            (dll.get_path)(i, buf.as_mut_ptr(), buf.len() as u32);
-            let end = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
-            let s = String::from_utf16_lossy(&buf[..end]);
-            let dir = ((dll.get_attr)(i) & 0x10) != 0;
-            out.push_str(&format!("{} {}\n", if dir { "[DIR]" } else { "[FILE]" }, s));
+/// Search failures; "no results" is not an error
+#[derive(Debug, thiserror::Error)]
+enum SearchError {
+    #[error("Lock: {0}")]
+    Lock(String),
+    #[error("DLL not loaded: {0}")]
+    NotLoaded(String),
+    #[error("Query: {0}")]
+    BadQuery(String),
+    #[error("Query failed: {0}")]
+    QueryFailed(EverythingError),
+    #[cfg_attr(not(windows), allow(dead_code))]
+    #[error("Query timed out after {0} ms")]
+    Timeout(u32),
+    #[cfg_attr(not(windows), allow(dead_code))]
+    #[error("IPC: {0}")]
+    Ipc(String),
+    #[error("{0}")]
+    Backend(String),
+    #[error("JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl From<SearchError> for McpError {
+    fn from(e: SearchError) -> Self {
+        match e {
+            SearchError::BadQuery(_) => McpError::invalid_params(e.to_string(), None),
+            // Symbolic name in data so clients can tell, say, IPC (not running) from out of memory
+            SearchError::QueryFailed(err) => McpError::internal_error(e.to_string(),
+                Some(serde_json::json!({ "everything_error": err.name(), "code": err.code() }))),
+            _ => McpError::internal_error(e.to_string(), None),
         }
-        out
     }
 }
 
+/// One search result; `index` is its position in the full result list (offset included)
+#[derive(Serialize)]
+struct Hit {
+    index: u32, path: String, name: String, extension: Option<String>, is_dir: bool, size: Option<u64>,
+    /// Dates are RFC 3339 UTC text; see filetime_to_rfc3339
+    date_modified: Option<String>, run_count: u32,
+    /// Only read when Opts::created is set
+    #[serde(skip_serializing_if = "Option::is_none")] date_created: Option<String>,
+    /// Only read when Opts::accessed is set
+    #[serde(skip_serializing_if = "Option::is_none")] date_accessed: Option<String>,
+    /// FILE_ATTRIBUTE_* bits; JSON gets {"readonly": bool, "hidden": bool, ...}
+    #[serde(serialize_with = "ser_attrs")] attributes: u32,
+    /// Name with Everything's *bold* markers, only when highlighting was requested
+    #[serde(skip)] highlighted: Option<String>,
+    /// `[start, len]` character ranges of `name` that matched the query
+    #[serde(skip_serializing_if = "Option::is_none")] matches: Option<Vec<[u32; 2]>>,
+    /// File list (.efu) the result comes from; only read when Opts::file_list is set, None for indexed volumes
+    #[serde(skip_serializing_if = "Option::is_none")] file_list: Option<String>,
+    /// File system state right now, only with everything_search's enrich
+    #[serde(skip_serializing_if = "Option::is_none")] live: Option<live::Live>,
+}
+
+/// FILE_ATTRIBUTE_* bits with the letters Everything's attrib: function uses for them
+const ATTRIBUTES: &[(u32, char, &str)] = &[
+    (0x1, 'R', "readonly"), (0x2, 'H', "hidden"), (0x4, 'S', "system"), (0x10, 'D', "directory"), (0x20, 'A', "archive"),
+    (0x100, 'T', "temporary"), (0x200, 'P', "sparse"), (0x400, 'L', "reparse"), (0x800, 'C', "compressed"),
+    (0x1000, 'O', "offline"), (0x2000, 'I', "notindexed"), (0x4000, 'E', "encrypted"),
+];
+
+/// 0x23 -> "RHA"
+fn attr_flags_to_string(attrs: u32) -> String {
+    ATTRIBUTES.iter().filter(|(bit, _, _)| attrs & bit != 0).map(|(_, c, _)| *c).collect()
+}
+
+fn ser_attrs<S: serde::Serializer>(attrs: &u32, s: S) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeMap;
+    let mut m = s.serialize_map(Some(ATTRIBUTES.len()))?;
+    for (bit, _, name) in ATTRIBUTES { m.serialize_entry(name, &(attrs & bit != 0))?; }
+    m.end()
+}
+
+/// "hidden, system" -> "HS"
+fn parse_attr_names(names: &str) -> Result<String, String> {
+    names.split(',').map(str::trim).filter(|n| !n.is_empty()).map(|n| {
+        ATTRIBUTES.iter().find(|(_, _, name)| name.eq_ignore_ascii_case(n)).map(|(_, c, _)| *c)
+            .ok_or_else(|| format!("Unknown attribute '{}' (expected {})", n, ATTRIBUTES.iter().map(|a| a.2).collect::<Vec<_>>().join(", ")))
+    }).collect()
+}
+
+/// Split Everything's highlighted name ("*foo*bar", "**" = literal *) into ranges of the plain name
+fn highlight_spans(hl: &str) -> Vec<[u32; 2]> {
+    let (mut spans, mut pos, mut start, mut cs) = (Vec::new(), 0u32, None, hl.chars().peekable());
+    while let Some(c) = cs.next() {
+        if c != '*' { pos += 1; continue; }
+        if cs.peek() == Some(&'*') { cs.next(); pos += 1; continue; }
+        match start.take() {
+            Some(st) if pos > st => spans.push([st, pos - st]),
+            Some(_) => {}
+            None => start = Some(pos),
+        }
+    }
+    spans
+}
+
+/// Query settings; the default is a plain search sorted by name ascending.
+/// `timeout_ms` of 0 waits for Everything indefinitely.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+struct Opts { max: u32, case: bool, word: bool, regex: bool, path: bool, sort: u32, offset: u32, timeout_ms: u32, highlight: bool, created: bool, accessed: bool, file_list: bool }
+
+impl Opts {
+    fn max(max: u32) -> Self { Self { max, ..Default::default() } }
+}
+
+/// Execute the configured query, waiting for the reply with a deadline when `timeout_ms` is set (Windows only)
+unsafe fn run_query(dll: &EvDll, timeout_ms: u32) -> Result<(), SearchError> {
+    #[cfg(windows)]
+    if timeout_ms > 0 { return ipc::query(dll, timeout_ms); }
+    #[cfg(not(windows))]
+    let _ = timeout_ms;
+    if (dll.query)(1) == 0 { return Err(SearchError::QueryFailed(EverythingError::from_code((dll.get_err)()))); }
+    Ok(())
+}
+
+/// Everything64.dll; the only backend with full query syntax
+struct EverythingSearcher;
+
+impl EverythingSearcher {
+    /// Configure and run a query, then read the results in the same job on the DLL thread (see worker.rs), so no
+    /// other query can replace the search state or result list in between.
+    /// An IPC failure is retried up to ipc_retries() times with a growing pause, other queries running meanwhile;
+    /// any other error fails straight away
+    async fn query<T: Send + 'static>(q: &str, o: &Opts, max: u32, flags: u32, read: impl FnOnce(&dyn EverythingApi) -> T + Copy + Send + 'static) -> Result<T, SearchError> {
+        let mut attempt = 0;
+        loop {
+            match Self::query_once(q.to_string(), *o, max, flags, read).await {
+                Err(SearchError::QueryFailed(EverythingError::Ipc)) if attempt < ipc_retries() => {
+                    attempt += 1;
+                    tracing::debug!(attempt, "Everything IPC unavailable, retrying");
+                    tokio::time::sleep(std::time::Duration::from_millis(100 * attempt as u64)).await;
+                }
+                r => return r,
+            }
+        }
+    }
+
+    async fn query_once<T: Send + 'static>(q: String, o: Opts, max: u32, flags: u32, read: impl FnOnce(&dyn EverythingApi) -> T + Send + 'static) -> Result<T, SearchError> {
+        worker::call(move |dll| {
+            let dll = dll.as_ref().map_err(|e| SearchError::NotLoaded(e.clone()))?;
+            run_search(dll, &q, &o, max, flags)?;
+            Ok(read(dll))
+        }).await?
+    }
+}
+
+/// Set every search setting from `o` and run the query
+fn run_search(api: &dyn EverythingApi, q: &str, o: &Opts, max: u32, flags: u32) -> Result<(), SearchError> {
+    let qw = U16CString::from_str(q).map_err(|e| SearchError::BadQuery(e.to_string()))?;
+    api.set_search(&qw);
+    api.set_max(max);
+    api.set_match_case(o.case);
+    api.set_match_whole_word(o.word);
+    api.set_regex(o.regex);
+    api.set_match_path(o.path);
+    api.set_request_flags(flags);
+    // Sort and offset are sticky inside the DLL, so always set them
+    api.set_sort(o.sort.max(1));
+    api.set_offset(o.offset);
+    api.query(o.timeout_ms)
+}
+
+/// REQUEST_* flags for a search with `o`
+fn request_flags(o: &Opts) -> u32 {
+    let mut flags = REQUEST_FILE_NAME | REQUEST_PATH | REQUEST_EXTENSION | REQUEST_SIZE | REQUEST_DATE_MODIFIED | REQUEST_ATTRIBUTES | REQUEST_RUN_COUNT;
+    if o.highlight { flags |= REQUEST_HIGHLIGHTED_FILE_NAME; }
+    if o.created { flags |= REQUEST_DATE_CREATED; }
+    if o.accessed { flags |= REQUEST_DATE_ACCESSED; }
+    if o.file_list { flags |= REQUEST_FILE_LIST_FILE_NAME; }
+    flags
+}
+
+/// The total and the current result page as hits, reading only what `o` asked for
+fn read_hits(api: &dyn EverythingApi, o: &Opts) -> (u32, Vec<Hit>) {
+    let hits = (0..api.num_results()).map(|i| {
+        let path = result_path(api, i);
+        // From the DLL rather than split off the path, which is wrong for roots like C:\
+        let name = api.file_name(i).unwrap_or_else(|| path.rsplit('\\').next().unwrap_or(&path).to_string());
+        let extension = api.extension(i).filter(|e| !e.is_empty());
+        let size = api.size(i).filter(|&n| n >= 0).map(|n| n as u64);
+        let date_modified = api.date_modified(i).and_then(filetime_to_rfc3339);
+        let date_created = if o.created { api.date_created(i).and_then(filetime_to_rfc3339) } else { None };
+        let date_accessed = if o.accessed { api.date_accessed(i).and_then(filetime_to_rfc3339) } else { None };
+        // An empty highlight means nothing usable came back; keep the plain name
+        let highlighted = if o.highlight { api.highlighted_file_name(i).filter(|h| !h.is_empty()) } else { None };
+        let matches = highlighted.as_deref().map(highlight_spans);
+        let attributes = api.attributes(i);
+        let file_list = if o.file_list { api.file_list_file_name(i).filter(|f| !f.is_empty()) } else { None };
+        Hit {
+            index: o.offset + i, path, name, extension, is_dir: (attributes & 0x10) != 0, size, date_modified, date_created, date_accessed,
+            run_count: api.run_count(i), attributes, highlighted, matches, file_list, live: None,
+        }
+    }).collect();
+    (api.total_results(), hits)
+}
+
+#[async_trait::async_trait]
+impl backend::FileSearcher for EverythingSearcher {
+    fn name(&self) -> &'static str { "Everything" }
+
+    async fn search(&self, q: &str, o: &Opts) -> Result<(u32, Vec<Hit>), SearchError> {
+        let o = *o;
+        Self::query(q, &o, o.max.clamp(1, 500), request_flags(&o), move |api| read_hits(api, &o)).await
+    }
+
+    /// Max 0 makes Everything send only the totals, no result list
+    async fn count(&self, q: &str, o: &Opts) -> Result<u32, SearchError> {
+        Self::query(q, &Opts { offset: 0, ..*o }, 0, REQUEST_FILE_NAME, |api| api.total_results()).await
+    }
+}
+
+/// Everything on Windows; Spotlight or locate elsewhere with reduced syntax (see backend.rs).
+/// EVERYTHING_MOCK_RESULTS swaps in a canned path list on any platform, for CI and debugging.
+static BACKEND: Lazy<Box<dyn backend::FileSearcher>> = Lazy::new(|| {
+    if let Some(f) = std::env::var("EVERYTHING_MOCK_RESULTS").ok().filter(|f| !f.is_empty()) {
+        return Box::new(backend::Mock(f));
+    }
+    match std::env::consts::OS {
+        "windows" => Box::new(EverythingSearcher),
+        "macos" => Box::new(backend::Spotlight),
+        _ => Box::new(backend::Locate),
+    }
+});
+
+#[tracing::instrument(level = "debug", skip(o), fields(backend = BACKEND.name()))]
+async fn fetch(q: &str, o: &Opts) -> Result<(u32, Vec<Hit>), SearchError> {
+    let start = std::time::Instant::now();
+    // A --template showing creation or access times needs them read for every search
+    let (created, accessed) = TEMPLATE.get().map_or((false, false), |t| t.dates());
+    let o = Opts { max: o.max.min(max_cap()), created: o.created || created, accessed: o.accessed || accessed, ..*o };
+    let r = BACKEND.search(q, &o).await;
+    match &r {
+        Ok((total, hits)) => tracing::debug!(total, shown = hits.len(), elapsed_ms = start.elapsed().as_millis() as u64, "search done"),
+        Err(e) => tracing::warn!(error = %e, elapsed_ms = start.elapsed().as_millis() as u64, "search failed"),
+    }
+    r
+}
+
+/// Drop hits that are the same file as an earlier hit under another spelling: letter case or an 8.3 short name on
+/// Windows, `..` or a symlink anywhere. Keeps the first of each, renumbers `index` so query_id lookups still line up,
+/// and returns how many were dropped. The paths are resolved on the blocking pool
+async fn dedup_hits(hits: &mut Vec<Hit>, offset: u32) -> usize {
+    let keys = pipeline::enrich(hits.iter().map(|h| h.path.clone()).collect(), *pipeline::WORKERS, |path: String| {
+        // canonicalize expands short names; a path it can't resolve is compared as given
+        let c = std::fs::canonicalize(&path).map_or(path, |c| c.to_string_lossy().into_owned());
+        let c = c.strip_prefix(r"\\?\").map(str::to_string).unwrap_or(c);
+        if cfg!(windows) { c.to_lowercase() } else { c }
+    }).await;
+    let before = hits.len();
+    let mut seen = std::collections::HashSet::new();
+    let mut keys = keys.into_iter();
+    hits.retain(|_| keys.next().is_some_and(|k| seen.insert(k)));
+    for (i, h) in hits.iter_mut().enumerate() { h.index = offset + i as u32; }
+    before - hits.len()
+}
+
+/// Most results everything_search_all will collect, whatever max_total asks for
+const SEARCH_ALL_LIMIT: u32 = 100_000;
+
+/// Every match up to `limit`, one offset page of `o.max` at a time. Stops early once the total reported by
+/// the first page is reached or a page comes back short
+async fn fetch_all(q: &str, o: &Opts, limit: u32) -> Result<(u32, Vec<Hit>), SearchError> {
+    let page = o.max.clamp(1, max_cap());
+    let (mut total, mut all) = (0, Vec::new());
+    while (all.len() as u32) < limit {
+        let want = page.min(limit - all.len() as u32);
+        let (t, hits) = fetch(q, &Opts { max: want, offset: o.offset + all.len() as u32, ..*o }).await?;
+        if all.is_empty() { total = t; }
+        let n = hits.len() as u32;
+        all.extend(hits);
+        if n < want || o.offset + all.len() as u32 >= total { break; }
+    }
+    Ok((total, all))
+}
+
+/// Total matches without reading any paths
+#[tracing::instrument(level = "debug", skip(o), fields(backend = BACKEND.name()))]
+async fn count(q: &str, o: &Opts) -> Result<u32, SearchError> {
+    let start = std::time::Instant::now();
+    let r = BACKEND.count(q, o).await;
+    match &r {
+        Ok(total) => tracing::debug!(total, elapsed_ms = start.elapsed().as_millis() as u64, "count done"),
+        Err(e) => tracing::warn!(error = %e, elapsed_ms = start.elapsed().as_millis() as u64, "count failed"),
+    }
+    r
+}
+
+/// Sizes for display in the units chosen by --si: 723 -> "723 B", 740352 -> "723 KB", 1610612736 -> "1.5 GB"
+fn format_size(n: u64) -> String { format_size_as(n, si_sizes()) }
+
+/// Whole bytes and KB, one decimal from MB up; 1024-based units, or 1000-based with `si`.
+/// Rounding carries into the next unit, so 1048575 is "1.0 MB" rather than "1024 KB"
+fn format_size_as(n: u64, si: bool) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let base = if si { 1000.0 } else { 1024.0 };
+    let (mut v, mut u) = (n as f64, 0);
+    while v >= base && u < UNITS.len() - 1 { v /= base; u += 1; }
+    let shown = |v: f64, u: usize| if u < 2 { v.round() } else { (v * 10.0).round() / 10.0 };
+    if u > 0 && shown(v, u) >= base && u < UNITS.len() - 1 { v /= base; u += 1; }
+    if u < 2 { format!("{} {}", shown(v, u), UNITS[u]) } else { format!("{:.1} {}", v, UNITS[u]) }
+}
+
+/// Text listing of a result window starting `offset` results into `total`, in the --template layout if one was given
+/// and without the header under --quiet
+fn format_hits(q: &str, total: u32, offset: u32, hits: &[Hit]) -> String {
+    format_hits_as(q, total, offset, hits, TEMPLATE.get(), !quiet())
+}
+
+/// format_hits with an explicit template (None is the built-in layout). Without `summary` only the result lines
+/// are written, so an empty page is an empty string
+fn format_hits_as(q: &str, total: u32, offset: u32, hits: &[Hit], tpl: Option<&template::Template>, summary: bool) -> String {
+    // Sized up front and written in place: no per-line temporaries or regrowth on 500-row pages
+    let mut out = String::with_capacity(64 + hits.iter().map(|h| h.path.len() + 64).sum::<usize>());
+    if !summary { write_lines(&mut out, hits, tpl); return out; }
+    if hits.is_empty() && total > 0 { return format!("Found {} (showing none: offset {} is past the end)", total, offset); }
+    if hits.is_empty() { return format!("No results for: {}", q); }
+    let _ = write!(out, "Found {} (showing {}-{} of {}):\n\n", total, offset + 1, offset + hits.len() as u32, total);
+    write_lines(&mut out, hits, tpl);
+    out
+}
+
+/// One newline-terminated line per hit
+fn write_lines(out: &mut String, hits: &[Hit], tpl: Option<&template::Template>) {
+    for h in hits {
+        if let Some(t) = tpl { t.render(h, out); out.push('\n'); continue; }
+        let size = h.size.map(format_size).unwrap_or_else(|| "-".into());
+        // Show the highlighted name in place of the plain one at the end of the path
+        let path = match &h.highlighted {
+            Some(hl) if h.path.ends_with(&h.name) => format!("{}{}", &h.path[..h.path.len() - h.name.len()], hl),
+            _ => h.path.clone(),
+        };
+        let attrs = if h.attributes == 0 { "-".into() } else { attr_flags_to_string(h.attributes) };
+        let _ = write!(out, "{} {:>9} {:<5} {}", if h.is_dir { "[DIR] " } else { "[FILE]" }, size, attrs, path);
+        if let Some(dm) = &h.date_modified { let _ = write!(out, " (modified {})", short_time(dm)); }
+        if let Some(dc) = &h.date_created { let _ = write!(out, " (created {})", short_time(dc)); }
+        out.push('\n');
+    }
+}
+
+/// Copies of one file name from a dupe: search
+#[derive(Serialize)]
+struct DupeGroup<'a> {
+    name: &'a str,
+    count: usize,
+    /// Every copy has the same known size: likely true duplicates
+    same_size: bool,
+    /// Bytes freed by keeping one copy; 0 unless same_size
+    reclaimable: u64,
+    files: Vec<&'a Hit>,
+}
+
+/// Group hits by name (case-insensitive), most reclaimable space first
+fn dupe_groups(hits: &[Hit]) -> Vec<DupeGroup<'_>> {
+    let mut by_name: Vec<(String, Vec<&Hit>)> = Vec::new();
+    for h in hits {
+        let key = h.name.to_lowercase();
+        match by_name.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => v.push(h),
+            None => by_name.push((key, vec![h])),
+        }
+    }
+    let mut groups: Vec<DupeGroup> = by_name.into_iter().map(|(_, files)| {
+        let first = files[0].size;
+        let same_size = first.is_some() && files.iter().all(|h| h.size == first);
+        let reclaimable = if same_size { first.unwrap_or(0) * (files.len() as u64 - 1) } else { 0 };
+        DupeGroup { name: &files[0].name, count: files.len(), same_size, reclaimable, files }
+    }).collect();
+    groups.sort_by(|a, b| b.reclaimable.cmp(&a.reclaimable).then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase())));
+    groups
+}
+
+/// dupe: results under one header per name, flagging groups whose sizes all match
+fn format_dupes(q: &str, total: u32, hits: &[Hit]) -> String {
+    if hits.is_empty() { return format!("No results for: {}", q); }
+    let groups = dupe_groups(hits);
+    let reclaim: u64 = groups.iter().map(|g| g.reclaimable).sum();
+    let mut out = format!("Found {} (showing {} in {} names, {} reclaimable from same-size copies):\n",
+        total, hits.len(), groups.len(), format_size(reclaim));
+    for g in &groups {
+        let kind = if g.same_size { format!("same size, {} reclaimable", format_size(g.reclaimable)) } else { "sizes differ".into() };
+        let _ = write!(out, "\n{} ({} copies, {})\n", g.name, g.count, kind);
+        for h in &g.files {
+            let size = h.size.map(format_size).unwrap_or_else(|| "-".into());
+            let dm = h.date_modified.as_deref().map_or_else(|| "-".into(), short_time);
+            let _ = writeln!(out, "  {:>9}  {}  {}", size, dm, h.path);
+        }
+    }
+    out
+}
+
+/// `"folder\" ext:a;b content:"text"` for content search; folder and extensions (comma-separated) are optional
+fn content_query(content: &str, folder: Option<&str>, extensions: Option<&str>) -> Result<String, String> {
+    let mut q = QueryBuilder::new();
+    if let Some(f) = folder.filter(|f| !f.trim().is_empty()) { q = q.in_folder(f); }
+    q.ext(extensions.unwrap_or("")).content(content).build()
+}
+
+/// With `dry_run`, the query a tool composed, to return in place of running it
+fn dry_run(q: &str, dry_run: Option<bool>) -> Option<CallToolResult> {
+    dry_run.unwrap_or(false).then(|| CallToolResult::success(vec![Content::text(format!("Dry run, not executed. Query:\n{}", q))]))
+}
+
+/// A trimmed path that exists, for tools that pass it to Everything rather than a shell
+fn existing_path(path: &str) -> Result<String, McpError> {
+    let path = path.trim();
+    if path.is_empty() { return Err(McpError::invalid_params("path is empty", None)); }
+    if !std::path::Path::new(path).exists() { return Err(McpError::invalid_params(format!("No such file or folder: {}", path), None)); }
+    Ok(path.to_string())
+}
+
+/// Validate a path a tool is about to hand to the OS: non-empty, existing, and free of shell metacharacters
+fn checked_path(path: &str) -> Result<&std::path::Path, McpError> {
+    let path = path.trim();
+    if path.is_empty() { return Err(McpError::invalid_params("path is empty", None)); }
+    if path.contains(['&', '|', '<', '>', '^', '%', '"', '`', ';', '$', '\n', '\r']) {
+        return Err(McpError::invalid_params(format!("Refusing path with shell metacharacters: {}", path), None));
+    }
+    let p = std::path::Path::new(path);
+    if !p.exists() { return Err(McpError::invalid_params(format!("No such file or folder: {}", path), None)); }
+    Ok(p)
+}
+
+/// Number of path components `path` sits below `base` (1 = immediate child), None if outside it
+fn depth_below(base: &str, path: &str) -> Option<usize> {
+    let base = base.trim_end_matches(['\\', '/']).replace('/', "\\");
+    let path = path.replace('/', "\\");
+    let head = path.get(..base.len())?;
+    if !head.eq_ignore_ascii_case(&base) { return None; }
+    let rest = path[base.len()..].strip_prefix('\\')?;
+    Some(rest.split('\\').filter(|c| !c.is_empty()).count())
+}
+
+/// JSON envelope for a page of hits. `total` is Everything_GetTotResults, `shown` the page size
+/// (Everything_GetNumResults), so clients can page with `offset`
+fn json_page(total: u32, offset: u32, hits: &[Hit]) -> serde_json::Value {
+    serde_json::json!({ "total": total, "shown": hits.len(), "offset": offset, "results": hits })
+}
+
+/// NDJSON form of a json_page envelope: one line per result, then the rest of the envelope as a final
+/// `{"type": "summary", ...}` line, so each line parses on its own
+fn ndjson(mut v: serde_json::Value) -> String {
+    let results = v.as_object_mut().and_then(|m| m.remove("results"));
+    let mut out = String::new();
+    for r in results.as_ref().and_then(|r| r.as_array()).into_iter().flatten() { let _ = writeln!(out, "{}", r); }
+    v["type"] = "summary".into();
+    let _ = writeln!(out, "{}", v);
+    out
+}
+
+/// A fetched page as {total, shown, offset, query_id, results: [{index, path, name, is_dir, size, ...}]}
+fn search_json(total: u32, offset: u32, hits: &[Hit]) -> serde_json::Value {
+    let mut v = json_page(total, offset, hits);
+    v["query_id"] = remember_results(offset, hits).into();
+    v
+}
+
+async fn search(q: &str, max: u32, case: bool, word: bool, regex: bool, path: bool) -> Result<String, SearchError> {
+    let (total, hits) = fetch(q, &Opts { max, case, word, regex, path, ..Default::default() }).await?;
+    Ok(format_hits(q, total, 0, &hits) + &cap_note(max, total))
+}
+
+/// Zero-result fallback: run the relaxations from explain::relaxations in order and report the first that matches
+async fn suggest(q: &str, o: &Opts) -> Result<String, SearchError> {
+    for (how, rq) in explain::relaxations(q) {
+        let (total, hits) = fetch(&rq, &Opts { regex: false, offset: 0, ..*o }).await?;
+        if !hits.is_empty() {
+            return Ok(format!("No exact matches for: {}\nDid you mean: {} ({}) - {} results\n\n{}", q, rq, how, total, format_hits(&rq, total, 0, &hits)));
+        }
+    }
+    Ok(format!("No results for: {} (no broader query matched either)", q))
+}
+
 // Parameter structs with Parameters wrapper pattern
-#[derive(Debug, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct SearchReq { 
     #[schemars(description = "Search query")] pub query: String,
     pub max_results: Option<u32>, pub match_case: Option<bool>, 
     pub whole_word: Option<bool>, pub regex: Option<bool>, pub match_path: Option<bool>,
+    #[schemars(description = "On zero results, try a broader query and report it (runs extra searches)")] pub suggest: Option<bool>,
+    #[schemars(description = "Output format: text (default), json, ndjson (one result per line, then a {\"type\": \"summary\"} line) or csv (header row, RFC 4180 quoting)")] pub format: Option<String>,
+    #[schemars(description = "Sort key: name, path, size, extension, date_modified, date_created, date_accessed, run_count")] pub sort_by: Option<String>,
+    #[schemars(description = "Sort ascending (default true)")] pub ascending: Option<bool>,
+    #[schemars(description = "Warn when sort_by has no fast-sort index in Everything (default true)")] pub warn_slow_sort: Option<bool>,
+    #[schemars(description = "Skip this many results, for paging (default 0)")] pub offset: Option<u32>,
+    #[schemars(description = "Give up if Everything hasn't answered within this many ms (default: wait)")] pub timeout_ms: Option<u32>,
+    #[schemars(description = "Reuse an identical search from the last few seconds (default true)")] pub cache: Option<bool>,
+    #[schemars(description = "Return only the number of matches, not the paths (much faster for large result sets)")] pub count_only: Option<bool>,
+    #[schemars(description = "Mark matched text in names: *bold* in text output, [start, len] ranges in JSON \"matches\"")] pub highlight: Option<bool>,
+    #[schemars(description = "Include each result's creation time")] pub show_created: Option<bool>,
+    #[schemars(description = "Layout of each text result line, e.g. \"{size}\\t{path}\". Placeholders: path, name, dir, ext, size, bytes, modified, created, accessed, attr, kind, index")] pub template: Option<String>,
+    #[schemars(description = "Start text output with the \"Found N (showing X-Y of N)\" header (default true); false gives result lines only")] pub summary: Option<bool>,
+    #[schemars(description = "Report how long the query took: (query took Nms) in text, elapsed_ms in JSON. Bypasses the cache")] pub timing: Option<bool>,
+    #[schemars(description = "Stat each result (up to 200) now and add its live size, modified time, permissions and symlink target, flagging files that are missing or changed since indexing. Text and JSON only; bypasses the cache")] pub enrich: Option<bool>,
+    #[schemars(description = "Drop results that are the same file as an earlier one under another spelling (letter case, 8.3 short name, symlink) and report how many went")] pub dedup: Option<bool>,
+    #[schemars(description = "Return the query that would be sent to Everything instead of running it (all query tools accept this)")] pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SaveSearchReq {
+    #[schemars(description = "Name to run it by with everything_run_saved")] pub name: String,
+    #[serde(flatten)] pub search: SearchReq,
+    #[schemars(description = "Replace a saved search with the same name (default false)")] pub overwrite: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RunSavedReq {
+    #[schemars(description = "Name given to everything_save_search")] pub name: String,
+    #[schemars(description = "Override the saved max_results")] pub max_results: Option<u32>,
+    #[schemars(description = "Override the saved offset, for paging")] pub offset: Option<u32>,
+    #[schemars(description = "Override the saved format")] pub format: Option<String>,
+    pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SearchAllReq {
+    #[schemars(description = "Search query")] pub query: String,
+    #[schemars(description = "Results fetched per round trip (default and max: the per-call cap, normally 500)")] pub page_size: Option<u32>,
+    #[schemars(description = "Stop after this many results (default 10000, hard limit 100000)")] pub max_total: Option<u32>,
+    pub match_case: Option<bool>, pub match_path: Option<bool>,
+    #[schemars(description = "Output format: text (default), json, or ndjson (one result per line, then a {\"type\": \"summary\"} line)")] pub format: Option<String>,
+    pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ExtReq { #[schemars(description = "Extensions")] pub extensions: String, pub keywords: Option<String>, pub max_results: Option<u32>, pub dry_run: Option<bool> }
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct KeyReq {
+    pub keywords: Option<String>, pub max_results: Option<u32>,
+    #[schemars(description = "Keywords must match whole words")] pub whole_word: Option<bool>,
+    #[schemars(description = "Match keywords against the full path instead of the name")] pub match_path: Option<bool>,
+    pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ChildrenReq {
+    #[schemars(description = "Folder whose direct children to list")] pub folder_path: String,
+    #[schemars(description = "Only children matching this query")] pub query: Option<String>,
+    pub max_results: Option<u32>, pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FileListReq {
+    #[schemars(description = "File list (.efu) name or full path as loaded in Everything; wildcards allowed, e.g. *photos*")] pub file_list: String,
+    #[schemars(description = "Only entries matching this query")] pub query: Option<String>,
+    pub max_results: Option<u32>, pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FolderReq { pub folder_path: String, pub query: String, pub max_results: Option<u32>, pub dry_run: Option<bool> }
+/// A list given either as a JSON array or as one comma-separated string
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum StrList { List(Vec<String>), Csv(String) }
+
+impl StrList {
+    fn items(&self) -> Vec<&str> {
+        match self {
+            StrList::List(v) => v.iter().map(|s| s.as_str()).collect(),
+            StrList::Csv(s) => s.split(',').collect(),
+        }
+    }
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SizeRangeReq {
+    #[schemars(description = "Smallest size, inclusive (e.g. 10mb, 1.5 GB); omit for no lower bound")] pub min: Option<String>,
+    #[schemars(description = "Largest size, inclusive; omit for no upper bound")] pub max: Option<String>,
+    pub keywords: Option<String>, pub max_results: Option<u32>, pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct BooleanReq {
+    #[schemars(description = "Terms of which at least one must match, as an array or comma-separated string")] pub include: Option<StrList>,
+    #[schemars(description = "Terms or folder paths (containing \\ or /) that must not match; a folder excludes its whole subtree")] pub exclude: Option<StrList>,
+    #[schemars(description = "Only search below this folder")] pub in_folder: Option<String>,
+    pub max_results: Option<u32>, pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FoldersInReq {
+    #[schemars(description = "Folders to search, as an array or comma-separated string")] pub folders: StrList,
+    pub query: String, pub max_results: Option<u32>, pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RecentReq { pub days: Option<u32>, pub extension: Option<String>, pub max_results: Option<u32>, pub dry_run: Option<bool> }
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RecentNlReq {
+    #[schemars(description = "e.g. \"last hour\", \"past 3 weeks\", \"since monday\", \"yesterday\", \"2 days ago\"")] pub phrase: String,
+    #[schemars(description = "modified (default) or created")] pub which: Option<String>,
+    pub extension: Option<String>, pub max_results: Option<u32>,
+    pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct AgeReq {
+    #[schemars(description = "How long ago: a number and a unit, e.g. 30min, 12h, 7d, 3w, 6mo, 1y")] pub duration: String,
+    #[schemars(description = "modified (default) or created")] pub which: Option<String>,
+    pub keywords: Option<String>, pub extension: Option<String>, pub max_results: Option<u32>,
+    pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct DateReq {
+    #[schemars(description = "today, yesterday, thisweek, last7days, 2024-01-31, >2024-01, 2024-01-01..2024-06-30, ...")] pub date_filter: String,
+    pub keywords: Option<String>, pub max_results: Option<u32>,
+    #[schemars(description = "Skip date_filter validation and pass it to Everything as-is")] pub unchecked: Option<bool>,
+    pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SizeReq { pub size_filter: String, pub keywords: Option<String>, pub max_results: Option<u32>, pub dry_run: Option<bool> }
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct DiskUsageReq {
+    #[schemars(description = "Folder to look in, including subfolders")] pub folder: String,
+    #[schemars(description = "Only these extensions, e.g. \"mp4,mkv\"")] pub ext: Option<String>,
+    pub max_results: Option<u32>, pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct LargeReq { pub min_size: Option<String>, pub file_type: Option<String>, pub max_results: Option<u32>, pub dry_run: Option<bool> }
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ContentReq { pub content: String, pub extensions: Option<String>, pub folder: Option<String>, pub max_results: Option<u32>, pub dry_run: Option<bool> }
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ContentPreviewReq {
+    #[schemars(description = "Text to find inside files")] pub content: String,
+    #[schemars(description = "Comma-separated extensions to search, e.g. rs,toml")] pub extensions: Option<String>,
+    #[schemars(description = "Only search below this folder")] pub folder: Option<String>,
+    pub max_results: Option<u32>,
+    #[schemars(description = "Matching lines shown per file (default 1)")] pub lines: Option<u32>,
+    #[schemars(description = "Lines of context before and after each match (default 0)")] pub context: Option<u32>,
+    #[schemars(description = "Characters per line; longer lines are clipped around the match (default 200)")] pub max_line_len: Option<u32>,
+    #[schemars(description = "KB read per file when looking for the line (default 1024)")] pub max_kb_per_file: Option<u64>,
+    pub match_case: Option<bool>,
+    pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RegexReq {
+    pub pattern: String, pub max_results: Option<u32>,
+    #[schemars(description = "Skip the syntax pre-check (e.g. for lookaround, which Everything may accept)")] pub unchecked: Option<bool>,
+    pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct DupeReq {
+    pub pattern: String, pub max_results: Option<u32>,
+    #[schemars(description = "Output format: text (default) or json")] pub format: Option<String>,
+    pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ExcludeReq { pub query: String, pub exclude: String, pub max_results: Option<u32>, pub dry_run: Option<bool> }
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct OrReq { pub terms: String, pub and_filter: Option<String>, pub max_results: Option<u32>, pub dry_run: Option<bool> }
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FoldersReq { pub query: String, pub max_results: Option<u32>, pub dry_run: Option<bool> }
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SyncReq {
+    #[schemars(description = "Client-chosen token identifying this sync stream")] pub sync_token: String,
+    #[schemars(description = "Search query")] pub query: String,
+    #[schemars(description = "On the first call for a token, return all matches (true) or none (false, default)")] pub initial_full: Option<bool>,
+    #[schemars(description = "Results read per page (default and upper limit: the server's result cap); all changes are returned")] pub max_results: Option<u32>,
+    pub dry_run: Option<bool>,
 }
 #[derive(Debug, Deserialize, JsonSchema)]
-pub struct ExtReq { #[schemars(description = "Extensions")] pub extensions: String, pub keywords: Option<String>, pub max_results: Option<u32> }
+pub struct IdenticalReq {
+    #[schemars(description = "Search query selecting files to compare")] pub query: String,
+    #[schemars(description = "Max matches fetched (default 500, or --default-max)")] pub max_files: Option<u32>,
+    #[schemars(description = "Max total MB read for hashing, largest same-size groups first (default 1024)")] pub max_hash_mb: Option<u64>,
+    pub dry_run: Option<bool>,
+}
 #[derive(Debug, Deserialize, JsonSchema)]
-pub struct KeyReq { pub keywords: Option<String>, pub max_results: Option<u32> }
+pub struct ListReq {
+    #[schemars(description = "Folder to list")] pub folder_path: String,
+    #[schemars(description = "List folders before files (default true)")] pub folders_first: Option<bool>,
+    pub max_results: Option<u32>,
+    pub dry_run: Option<bool>,
+}
 #[derive(Debug, Deserialize, JsonSchema)]
-pub struct FolderReq { pub folder_path: String, pub query: String, pub max_results: Option<u32> }
+pub struct CollectReq {
+    #[schemars(description = "Search query selecting files to collect")] pub query: String,
+    #[schemars(description = "Zip file to create")] pub target_path: String,
+    #[schemars(description = "Replace target_path if it already exists (default false: an existing file is an error)")] pub overwrite: Option<bool>,
+    #[schemars(description = "Folder entries are stored relative to (default: common parent of the matches)")] pub base_folder: Option<String>,
+    #[schemars(description = "Max files in the archive (default 100)")] pub max_files: Option<u32>,
+    #[schemars(description = "Max total uncompressed MB (default 100)")] pub max_total_mb: Option<u64>,
+    pub max_results: Option<u32>,
+    pub dry_run: Option<bool>,
+}
 #[derive(Debug, Deserialize, JsonSchema)]
-pub struct RecentReq { pub days: Option<u32>, pub extension: Option<String>, pub max_results: Option<u32> }
+pub struct MojibakeReq {
+    #[schemars(description = "Query to scan (default: every non-ASCII name)")] pub query: Option<String>,
+    #[schemars(description = "Minimum confidence 0.0-1.0 to report (default 0.5)")] pub min_confidence: Option<f32>,
+    pub max_results: Option<u32>,
+    pub dry_run: Option<bool>,
+}
 #[derive(Debug, Deserialize, JsonSchema)]
-pub struct DateReq { pub date_filter: String, pub keywords: Option<String>, pub max_results: Option<u32> }
+pub struct GlobReq {
+    #[schemars(description = "Folder the glob is relative to")] pub folder: String,
+    #[schemars(description = "Glob, e.g. **/*.test.ts or *.{ts,tsx}")] pub glob: String,
+    pub max_results: Option<u32>,
+    pub dry_run: Option<bool>,
+}
 #[derive(Debug, Deserialize, JsonSchema)]
-pub struct SizeReq { pub size_filter: String, pub keywords: Option<String>, pub max_results: Option<u32> }
+pub struct AnyGlobReq {
+    #[schemars(description = "Glob matched anywhere, e.g. **/*.rs, src/*.toml or docs/**/*.{md,txt}")] pub glob: String,
+    pub max_results: Option<u32>, pub dry_run: Option<bool>,
+}
 #[derive(Debug, Deserialize, JsonSchema)]
-pub struct LargeReq { pub min_size: Option<String>, pub file_type: Option<String>, pub max_results: Option<u32> }
+pub struct RenameReq {
+    #[schemars(description = "Search query selecting files")] pub query: String,
+    #[schemars(description = "Regex applied to each file name (not the folder)")] pub pattern: String,
+    #[schemars(description = "Replacement; $1, ${name} insert capture groups")] pub replacement: String,
+    pub max_results: Option<u32>,
+    pub dry_run: Option<bool>,
+}
 #[derive(Debug, Deserialize, JsonSchema)]
-pub struct ContentReq { pub content: String, pub extensions: Option<String>, pub folder: Option<String>, pub max_results: Option<u32> }
+pub struct MostUsedReq {
+    pub keywords: Option<String>,
+    #[schemars(description = "Only files opened at least this many times (default 1)")] pub min_runs: Option<u32>,
+    pub max_results: Option<u32>,
+    pub dry_run: Option<bool>,
+}
 #[derive(Debug, Deserialize, JsonSchema)]
-pub struct RegexReq { pub pattern: String, pub max_results: Option<u32> }
+pub struct PathReq {
+    #[schemars(description = "Full path of a file or folder")] pub path: Option<String>,
+    #[schemars(description = "query_id of an everything_search JSON result, used with index instead of path")] pub query_id: Option<String>,
+    #[schemars(description = "index of the result within that query_id")] pub index: Option<u32>,
+}
 #[derive(Debug, Deserialize, JsonSchema)]
-pub struct DupeReq { pub pattern: String, pub max_results: Option<u32> }
+pub struct OpenReq {
+    #[serde(flatten)] pub target: PathReq,
+    #[schemars(description = "Also add one to the file's Everything run count, as everything_mark_used does (default false; skipped unless writes are enabled)")] pub mark_used: Option<bool>,
+}
 #[derive(Debug, Deserialize, JsonSchema)]
-pub struct ExcludeReq { pub query: String, pub exclude: String, pub max_results: Option<u32> }
+pub struct SetRunCountReq {
+    #[serde(flatten)] pub target: PathReq,
+    #[schemars(description = "New run count")] pub run_count: u32,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct AttrReq {
+    #[schemars(description = "Comma-separated attributes files must have: hidden, system, readonly, archive, compressed, encrypted, ...")] pub include: Option<String>,
+    #[schemars(description = "Comma-separated attributes files must not have")] pub exclude: Option<String>,
+    pub keywords: Option<String>, pub max_results: Option<u32>,
+    pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ExactNameReq {
+    #[schemars(description = "Whole file name including extension, e.g. config.json")] pub filename: String,
+    pub match_case: Option<bool>, pub max_results: Option<u32>, pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FilenameReq {
+    #[schemars(description = "Search query; plain terms and phrases match the file name only")] pub query: String,
+    #[schemars(description = "Include folders whose own name matches (default false)")] pub include_folders: Option<bool>,
+    pub match_case: Option<bool>, pub max_results: Option<u32>, pub dry_run: Option<bool>,
+}
 #[derive(Debug, Deserialize, JsonSchema)]
-pub struct OrReq { pub terms: String, pub and_filter: Option<String>, pub max_results: Option<u32> }
+pub struct StaleReq {
+    #[schemars(description = "Only files last accessed at least this many days ago (default 365)")] pub min_age_days: Option<u32>,
+    pub keywords: Option<String>, pub max_results: Option<u32>, pub dry_run: Option<bool>,
+}
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GroupReq {
+    #[schemars(description = "Search query")] pub query: String,
+    #[schemars(description = "How many matches to tally (default 1000, max 10000)")] pub max_results: Option<u32>,
+    pub dry_run: Option<bool>,
+}
 #[derive(Debug, Deserialize, JsonSchema)]
-pub struct FoldersReq { pub query: String, pub max_results: Option<u32> }
+pub struct ExplainReq { #[schemars(description = "Raw Everything query to analyze")] pub query: String }
 
 // Server implementation
 #[derive(Clone)]
 pub struct EvMcp { tool_router: ToolRouter<Self> }
 
+impl Default for EvMcp {
+    fn default() -> Self { Self::new() }
+}
+
 #[tool_router]
 impl EvMcp {
     pub fn new() -> Self { Self { tool_router: Self::tool_router() } }
 
-    #[tool(description = "Search files/folders. Supports wildcards, ext:, paths, regex.")]
+    #[tool(description = "Search files/folders. Supports wildcards, ext:, paths, regex. On macOS/Linux (mdfind/locate) only terms, wildcards, ext:, file: and folder: work.")]
     async fn everything_search(&self, Parameters(p): Parameters<SearchReq>) -> Result<CallToolResult, McpError> {
-        Ok(CallToolResult::success(vec![Content::text(search(&p.query, p.max_results.unwrap_or(50), p.match_case.unwrap_or(false), p.whole_word.unwrap_or(false), p.regex.unwrap_or(false), p.match_path.unwrap_or(false)))]))
+        let sort = match p.sort_by.as_deref() {
+            Some(k) => sort_code(k, p.ascending.unwrap_or(true)).map_err(|e| McpError::invalid_params(e, None))?,
+            None => 0,
+        };
+        let o = Opts {
+            max: p.max_results.unwrap_or_else(|| default_max(50)), case: p.match_case.unwrap_or(false), word: p.whole_word.unwrap_or(false),
+            regex: p.regex.unwrap_or(false), path: p.match_path.unwrap_or(false), sort, offset: p.offset.unwrap_or(0),
+            timeout_ms: p.timeout_ms.unwrap_or(0), highlight: p.highlight.unwrap_or(false), created: p.show_created.unwrap_or(false), accessed: false, file_list: false,
+        };
+        let tpl = p.template.as_deref().map(template::Template::parse).transpose().map_err(|e| McpError::invalid_params(e, None))?;
+        let o = match &tpl {
+            Some(t) => { let (created, accessed) = t.dates(); Opts { created: o.created || created, accessed, ..o } }
+            None => o,
+        };
+        let fmt = p.format.clone().unwrap_or_else(|| "text".into());
+        if !matches!(&*fmt, "text" | "json" | "ndjson" | "csv") {
+            return Err(McpError::invalid_params(format!("Unknown format '{}' (expected \"text\", \"json\", \"ndjson\" or \"csv\")", fmt), None));
+        }
+        // CSV always has a date_created column
+        let o = Opts { created: o.created || fmt == "csv", ..o };
+        if let Some(r) = dry_run(&p.query, p.dry_run) { return Ok(r); }
+        let ttl = cache_ttl();
+        // A cached answer would report a stale time
+        let timing = p.timing.unwrap_or(false);
+        let enrich = p.enrich.unwrap_or(false);
+        let use_cache = p.cache.unwrap_or(true) && !ttl.is_zero() && !timing && !enrich;
+        let count_only = p.count_only.unwrap_or(false);
+        let mode = if count_only { "+count" } else if p.suggest.unwrap_or(false) { "+suggest" } else { "" };
+        let summary = p.summary.unwrap_or(!quiet());
+        let dedup = p.dedup.unwrap_or(false) && !count_only;
+        let key = (p.query.clone(), o, format!("{}{}{}{}{}", fmt, mode, if summary { "" } else { "-summary" }, if dedup { "+dedup" } else { "" }, p.template.as_deref().unwrap_or("")));
+        if use_cache {
+            if let Some((hit, set)) = CACHE.lock().ok().and_then(|mut c| c.get(&key, ttl)) {
+                if let (Some((id, set)), Ok(mut c)) = (set, RESULT_SETS.lock()) { c.put(id, set); }
+                return Ok(CallToolResult::success(vec![Content::text(hit)]));
+            }
+        }
+        let slow_sort = p.sort_by.is_some() && !count_only && p.warn_slow_sort.unwrap_or(true) && fast_sort(sort).await == Some(false);
+        let slow = p.sort_by.as_deref()
+            .filter(|_| slow_sort)
+            .map(|k| format!("Warning: Everything has no fast sort for {0}, so every match was sorted. \
+                Enable it under Tools > Options > Indexes (index the {0} and tick its fast sort).", k.replace('_', " ")));
+        let start = std::time::Instant::now();
+        // (total, hits, duplicates removed, results stat'd); None when only counting
+        let page = if count_only { None } else {
+            let (total, mut hits) = fetch(&p.query, &o).await?;
+            let removed = if dedup { dedup_hits(&mut hits, o.offset).await } else { 0 };
+            let checked = if enrich { live::check(&mut hits, live::LIMIT).await } else { 0 };
+            Some((total, hits, removed, checked))
+        };
+        let mut registered = None;
+        let r = if fmt == "csv" {
+            match page {
+                None => format!("count\n{}\n", count(&p.query, &o).await?),
+                Some((_, hits, _, _)) => format_csv(&hits),
+            }
+        } else if fmt != "text" {
+            let mut v = match page {
+                None => serde_json::json!({ "count": count(&p.query, &o).await? }),
+                Some((total, hits, removed, _)) => {
+                    let mut v = search_json(total, o.offset, &hits);
+                    registered = v["query_id"].as_str().map(|id| (id.to_string(), (o.offset, hits.iter().map(|h| h.path.clone()).collect())));
+                    if dedup { v["duplicates_removed"] = removed.into(); }
+                    v
+                }
+            };
+            if let Some(w) = &slow { v["warning"] = w.as_str().into(); }
+            if timing { v["elapsed_ms"] = (start.elapsed().as_millis() as u64).into(); }
+            if fmt == "ndjson" { ndjson(v) } else { v.to_string() }
+        } else {
+            let mut t = match page {
+                None => format!("{} matches for '{}'", count(&p.query, &o).await?, p.query),
+                Some((0, _, _, _)) if p.suggest.unwrap_or(false) && !o.regex => suggest(&p.query, &o).await?,
+                Some((total, hits, _, _)) if !summary => format_hits_as(&p.query, total, o.offset, &hits, tpl.as_ref().or(TEMPLATE.get()), false),
+                Some((total, hits, removed, checked)) => {
+                    let mut t = format_hits_as(&p.query, total, o.offset, &hits, tpl.as_ref().or(TEMPLATE.get()), true) + &cap_note(o.max, total);
+                    if removed > 0 { let _ = write!(t, "\n({} duplicate path(s) removed)", removed); }
+                    if enrich { t.push_str(&live::report(&hits, checked)); }
+                    t
+                }
+            };
+            if let Some(w) = &slow { t.push_str(&format!("\n{}", w)); }
+            if timing { t.push_str(&format!("\n(query took {}ms)", start.elapsed().as_millis())); }
+            t
+        };
+        if use_cache { if let Ok(mut c) = CACHE.lock() { c.put(key, (r.clone(), registered)); } }
+        Ok(CallToolResult::success(vec![Content::text(r)]))
+    }
+
+    #[tool(description = "Save everything_search arguments under a name, to rerun later with everything_run_saved")]
+    async fn everything_save_search(&self, Parameters(p): Parameters<SaveSearchReq>) -> Result<CallToolResult, McpError> {
+        let name = p.name.trim();
+        if name.is_empty() { return Err(McpError::invalid_params("name is empty", None)); }
+        // Catch what everything_search would reject now rather than on every later run
+        if let Some(k) = p.search.sort_by.as_deref() { sort_code(k, true).map_err(|e| McpError::invalid_params(e, None))?; }
+        if let Some(t) = p.search.template.as_deref() { template::Template::parse(t).map_err(|e| McpError::invalid_params(e, None))?; }
+        let path = saved_path()?;
+        // dry_run belongs to a run, not to the search
+        let entry = serde_json::to_value(SearchReq { dry_run: None, ..p.search }).map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        let replaced = saved::store(&path, name, entry, p.overwrite.unwrap_or(false)).map_err(|e| McpError::invalid_params(e, None))?;
+        let r = format!("{} saved search '{}' in {}", if replaced { "Replaced" } else { "Added" }, name, path.display());
+        Ok(CallToolResult::success(vec![Content::text(r)]))
+    }
+
+    #[tool(description = "Run a search saved with everything_save_search, optionally overriding max_results, offset or format")]
+    async fn everything_run_saved(&self, Parameters(p): Parameters<RunSavedReq>) -> Result<CallToolResult, McpError> {
+        let path = saved_path()?;
+        let all = saved::load(&path).map_err(|e| McpError::internal_error(e, None))?;
+        let Some(entry) = all.get(p.name.trim()) else {
+            let names: Vec<&str> = all.keys().map(String::as_str).collect();
+            let known = if names.is_empty() { "none saved yet".into() } else { format!("saved: {}", names.join(", ")) };
+            return Err(McpError::invalid_params(format!("No saved search named '{}' ({})", p.name.trim(), known), None));
+        };
+        let mut req: SearchReq = serde_json::from_value(entry.clone())
+            .map_err(|e| McpError::internal_error(format!("{}: saved search '{}': {}", path.display(), p.name.trim(), e), None))?;
+        req.max_results = p.max_results.or(req.max_results);
+        req.offset = p.offset.or(req.offset);
+        req.format = p.format.or(req.format);
+        req.dry_run = p.dry_run.or(req.dry_run);
+        self.everything_search(Parameters(req)).await
+    }
+
+    #[tool(description = "List saved searches as JSON: the file they live in and each name with its everything_search arguments")]
+    async fn everything_list_saved(&self) -> Result<CallToolResult, McpError> {
+        let path = saved_path()?;
+        let all = saved::load(&path).map_err(|e| McpError::internal_error(e, None))?;
+        let text = serde_json::to_string_pretty(&serde_json::json!({ "path": path, "searches": all }))
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+
+    #[tool(description = "Every match beyond the per-call cap, fetched page by page. Opt-in and potentially slow and large: \
+        stops at max_total (default 10000, at most 100000) and says so when results were cut off")]
+    async fn everything_search_all(&self, Parameters(p): Parameters<SearchAllReq>) -> Result<CallToolResult, McpError> {
+        let fmt = p.format.as_deref().unwrap_or("text");
+        if !matches!(fmt, "text" | "json" | "ndjson") {
+            return Err(McpError::invalid_params(format!("Unknown format '{}' (expected \"text\", \"json\" or \"ndjson\")", fmt), None));
+        }
+        if let Some(r) = dry_run(&p.query, p.dry_run) { return Ok(r); }
+        let o = Opts { max: p.page_size.unwrap_or(max_cap()), case: p.match_case.unwrap_or(false), path: p.match_path.unwrap_or(false), ..Default::default() };
+        let limit = p.max_total.unwrap_or(10_000).clamp(1, SEARCH_ALL_LIMIT);
+        let (total, hits) = fetch_all(&p.query, &o, limit).await?;
+        let truncated = (hits.len() as u32) < total && hits.len() as u32 == limit;
+        let warning = truncated.then(|| format!("Warning: stopped at {} of {} matches (max_total); narrow the query or raise max_total", limit, total));
+        let r = if fmt != "text" {
+            let mut v = json_page(total, 0, &hits);
+            if let Some(w) = &warning { v["warning"] = w.as_str().into(); }
+            if fmt == "ndjson" { ndjson(v) } else { v.to_string() }
+        } else {
+            let mut t = format_hits(&p.query, total, 0, &hits);
+            if let Some(w) = &warning { t.push_str(&format!("\n{}\n", w)); }
+            t
+        };
+        Ok(CallToolResult::success(vec![Content::text(r)]))
+    }
+
+    #[tool(description = "Diagnostics as JSON: loaded DLL path, version, database state, fast sorts, indexed item count and settings")]
+    async fn everything_diagnostics(&self) -> Result<CallToolResult, McpError> {
+        let mut d = serde_json::json!({
+            "backend": BACKEND.name(),
+            "config": { "max_cap": max_cap(), "cache_ttl_s": cache_ttl().as_secs(), "categories": CATEGORIES_PATH.get() },
+        });
+        if BACKEND.name() != "Everything" {
+            d["status"] = BACKEND.status().unwrap_or_else(|e| format!("Not available: {}", e)).into();
+        } else {
+            let base = d.clone();
+            d = worker::call(move |g| {
+                match g.as_ref() {
+                    Ok(dll) => unsafe {
+                        let ver: Vec<u32> = dll.get_ver.iter().map(|f| f()).collect();
+                        d["dll_path"] = dll.path.clone().into();
+                        // All zero when Everything isn't running
+                        d["version"] = (ver[0] > 0).then(|| format!("{}.{}.{}.{}", ver[0], ver[1], ver[2], ver[3])).into();
+                        d["db_loaded"] = ((dll.is_loaded)() != 0).into();
+                        d["fast_sort"] = dll.is_fast_sort.as_ref().map(|f| {
+                            ["name", "path", "size", "extension", "date_created", "date_modified", "date_accessed", "run_count"].iter()
+                                .filter_map(|k| sort_code(k, true).ok().map(|c| (k.to_string(), serde_json::Value::Bool(f(c) != 0))))
+                                .collect::<serde_json::Map<_, _>>()
+                        }).into();
+                    },
+                    Err(e) => d["dll_error"] = e.clone().into(),
+                }
+                d
+            }).await.unwrap_or_else(|e| {
+                let mut d = base;
+                d["dll_error"] = e.to_string().into();
+                d
+            });
+            // An empty query matches every indexed file and folder
+            match count("", &Opts::default()).await {
+                Ok(n) => d["indexed_items"] = n.into(),
+                Err(e) => d["query_error"] = e.to_string().into(),
+            }
+        }
+        let text = serde_json::to_string_pretty(&d).map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+
+    #[tool(description = "Load Everything64.dll again, e.g. after starting Everything when the server came up without it")]
+    async fn everything_reload(&self) -> Result<CallToolResult, McpError> {
+        let r = reload_dll().await.unwrap_or_else(|e| e);
+        Ok(CallToolResult::success(vec![Content::text(r)]))
+    }
+
+    #[tool(description = "Check prerequisites step by step (DLL found, Everything running, index loaded, a test query) with a fix for each failure")]
+    async fn everything_doctor(&self) -> Result<CallToolResult, McpError> {
+        let (_, report) = doctor::run().await;
+        Ok(CallToolResult::success(vec![Content::text(report)]))
     }
 
     #[tool(description = "Check Everything status")]
     async fn everything_status(&self) -> Result<CallToolResult, McpError> {
-        let r = match DLL.lock() {
-            Ok(g) => match g.as_ref() {
-                Some(dll) => unsafe {
-                    if (dll.is_loaded)() != 0 {
-                        format!("v{}.{}.{}.{} Ready", (dll.get_ver[0])(), (dll.get_ver[1])(), (dll.get_ver[2])(), (dll.get_ver[3])())
-                    } else { "Not available".into() }
-                },
-                None => "DLL not loaded".into(),
+        if BACKEND.name() != "Everything" {
+            let r = BACKEND.status().unwrap_or_else(|e| format!("Not available: {}", e));
+            return Ok(CallToolResult::success(vec![Content::text(format!("{} fallback: {}", BACKEND.name(), r))]));
+        }
+        let r = worker::call(|g| match g.as_ref() {
+            Ok(dll) => unsafe {
+                if (dll.is_loaded)() != 0 {
+                    format!("v{}.{}.{}.{} Ready", (dll.get_ver[0])(), (dll.get_ver[1])(), (dll.get_ver[2])(), (dll.get_ver[3])())
+                } else { "Not available".into() }
             },
-            Err(e) => format!("Error: {}", e),
-        };
+            Err(e) => format!("DLL not loaded: {}", e),
+        }).await.unwrap_or_else(|e| e.to_string());
         Ok(CallToolResult::success(vec![Content::text(r)]))
     }
 
+    #[tool(description = "Everything version as JSON {major, minor, revision, build, is_db_loaded} for feature checks; numbers are null when Everything isn't available")]
+    async fn everything_version(&self) -> Result<CallToolResult, McpError> {
+        let text = serde_json::to_string_pretty(&version_json().await).map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+
+
     #[tool(description = "Search by extension(s)")]
     async fn everything_search_ext(&self, Parameters(p): Parameters<ExtReq>) -> Result<CallToolResult, McpError> {
-        let eq: String = p.extensions.split(',').map(|e| format!("ext:{}", e.trim().trim_start_matches('.'))).collect::<Vec<_>>().join(" | ");
-        let q = p.keywords.filter(|k| !k.is_empty()).map(|k| format!("({}) {}", eq, k)).unwrap_or(eq);
-        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or(50), false, false, false, false))]))
+        let q = build(QueryBuilder::new().ext(&p.extensions).keywords(p.keywords.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, false).await?)]))
+    }
+
+    #[tool(description = "Search audio files")]
+    async fn everything_search_audio(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().ext(&category_exts("audio")).keywords(p.keywords.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, p.whole_word.unwrap_or(false), false, p.match_path.unwrap_or(false)).await?)]))
+    }
+
+    #[tool(description = "Search video files")]
+    async fn everything_search_video(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().ext(&category_exts("video")).keywords(p.keywords.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, p.whole_word.unwrap_or(false), false, p.match_path.unwrap_or(false)).await?)]))
+    }
+
+    #[tool(description = "Search image files")]
+    async fn everything_search_image(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().ext(&category_exts("image")).keywords(p.keywords.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, p.whole_word.unwrap_or(false), false, p.match_path.unwrap_or(false)).await?)]))
+    }
+
+    #[tool(description = "Search documents")]
+    async fn everything_search_doc(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().ext(&category_exts("doc")).keywords(p.keywords.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, p.whole_word.unwrap_or(false), false, p.match_path.unwrap_or(false)).await?)]))
+    }
+
+    #[tool(description = "Search code files")]
+    async fn everything_search_code(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().ext(&category_exts("code")).keywords(p.keywords.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, p.whole_word.unwrap_or(false), false, p.match_path.unwrap_or(false)).await?)]))
+    }
+
+    #[tool(description = "Search archives")]
+    async fn everything_search_archive(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().ext(&category_exts("archive")).keywords(p.keywords.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, p.whole_word.unwrap_or(false), false, p.match_path.unwrap_or(false)).await?)]))
+    }
+
+    #[tool(description = "Search executables")]
+    async fn everything_search_exe(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().ext(&category_exts("exe")).keywords(p.keywords.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, p.whole_word.unwrap_or(false), false, p.match_path.unwrap_or(false)).await?)]))
+    }
+
+    #[tool(description = "Search in folder")]
+    async fn everything_search_in_folder(&self, Parameters(p): Parameters<FolderReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().in_folder(&p.folder_path).raw(&p.query))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, false).await?)]))
+    }
+
+    #[tool(description = "Search in several folders at once (any of them)")]
+    async fn everything_search_in_folders(&self, Parameters(p): Parameters<FoldersInReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().in_any_folder(&p.folders.items()).raw(&p.query))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, false).await?)]))
+    }
+
+    #[tool(description = "Search file names only, never parent folders: 'report' skips C:\\reports\\jan.xlsx, and 'src\\main' \
+        only matches a name containing that text. Functions like ext: work as usual. Example: {query: \"invoice ext:pdf\"}")]
+    async fn everything_search_filename(&self, Parameters(p): Parameters<FilenameReq>) -> Result<CallToolResult, McpError> {
+        let terms = explain::name_only(&p.query).map_err(|e| McpError::invalid_params(e, None))?;
+        let q = if p.include_folders.unwrap_or(false) { QueryBuilder::new() } else { QueryBuilder::new().files_only() };
+        let q = build(q.raw(&terms))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), p.match_case.unwrap_or(false), false, false, false).await?)]))
+    }
+
+    #[tool(description = "Every file or folder with exactly this name (wfn:), listed by directory")]
+    async fn everything_search_exact_name(&self, Parameters(p): Parameters<ExactNameReq>) -> Result<CallToolResult, McpError> {
+        let name = p.filename.trim();
+        if name.is_empty() { return Err(McpError::invalid_params("filename is empty", None)); }
+        if name.contains(['\\', '/']) { return Err(McpError::invalid_params(format!("filename must be a bare name without path separators: {}", name), None)); }
+        let q = build(QueryBuilder::new().func("wfn", name))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        let o = Opts { max: p.max_results.unwrap_or_else(|| default_max(50)), case: p.match_case.unwrap_or(false), sort: 3, ..Default::default() };
+        let (total, hits) = fetch(&q, &o).await?;
+        if hits.is_empty() { return Ok(CallToolResult::success(vec![Content::text(format!("No files named {}", name))])); }
+        let mut out = format!("{} named {} (showing {}):\n\n", total, name, hits.len());
+        for h in &hits {
+            let dir = std::path::Path::new(&h.path).parent().map(|d| d.display().to_string()).unwrap_or_default();
+            let size = if h.is_dir { "[DIR]".into() } else { h.size.map(format_size).unwrap_or_else(|| "-".into()) };
+            let dm = h.date_modified.as_deref().map_or_else(|| "-".into(), short_time);
+            out.push_str(&format!("{:>9}  {}  {}\n", size, dm, dir));
+        }
+        Ok(CallToolResult::success(vec![Content::text(out)]))
+    }
+
+    #[tool(description = "Folders whose full path contains the query anywhere, e.g. 'node_modules' lists every folder at or \
+        below any node_modules. Unlike everything_search_in_folder, no starting folder is needed and the match is on path segments, not names")]
+    async fn everything_search_path_only(&self, Parameters(p): Parameters<FoldersReq>) -> Result<CallToolResult, McpError> {
+        if p.query.trim().is_empty() { return Err(McpError::invalid_params("query is empty", None)); }
+        let q = build(QueryBuilder::new().folders_only().raw(&p.query))?;
+        if let Some(r) = dry_run(&format!("{}  (match path)", q), p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, true).await?)]))
+    }
+
+    #[tool(description = "Search folders only")]
+    async fn everything_search_folders(&self, Parameters(p): Parameters<FoldersReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().folders_only().raw(&p.query))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, false).await?)]))
+    }
+
+    #[tool(description = "Direct children of a folder (one level, not the subtree) from the index, folders first, optionally filtered by a query")]
+    async fn everything_list_children(&self, Parameters(p): Parameters<ChildrenReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().parent(&p.folder_path).keywords(p.query.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        let max = p.max_results.unwrap_or_else(|| default_max(50));
+        let (total, mut hits) = fetch(&q, &Opts::max(max)).await?;
+        // Stable, so each group keeps Everything's name order
+        hits.sort_by_key(|h| !h.is_dir);
+        let mut out = format_hits(&q, total, 0, &hits);
+        out.push_str(&cap_note(max, total));
+        Ok(CallToolResult::success(vec![Content::text(out)]))
+    }
+
+    #[tool(description = "Entries of an Everything file list (.efu) via filelistfilename:, optionally filtered by a query. Shows which file list each match came from")]
+    async fn everything_search_filelist(&self, Parameters(p): Parameters<FileListReq>) -> Result<CallToolResult, McpError> {
+        if p.file_list.trim().is_empty() { return Err(McpError::invalid_params("file_list must not be empty", None)); }
+        let q = build(QueryBuilder::new().func("filelistfilename", &p.file_list).keywords(p.query.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        let max = p.max_results.unwrap_or_else(|| default_max(50));
+        let (total, hits) = fetch(&q, &Opts { file_list: true, ..Opts::max(max) }).await?;
+        // A wildcard can match several lists; count this page's entries per list
+        let mut lists = std::collections::BTreeMap::new();
+        for h in &hits { *lists.entry(h.file_list.as_deref().unwrap_or("-")).or_insert(0) += 1; }
+        let mut out = if lists.is_empty() {
+            format!("File list: {}\n", p.file_list)
+        } else {
+            let names: Vec<_> = lists.iter().map(|(l, n)| format!("{} ({})", l, n)).collect();
+            format!("File list(s): {}\n", names.join(", "))
+        };
+        out.push_str(&format_hits(&q, total, 0, &hits));
+        out.push_str(&cap_note(max, total));
+        Ok(CallToolResult::success(vec![Content::text(out)]))
+    }
+
+    #[tool(description = "Recently modified files")]
+    async fn everything_recent(&self, Parameters(p): Parameters<RecentReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().modified(&format!("last{}days", p.days.unwrap_or(1))).ext(p.extension.as_deref().unwrap_or("")))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, false).await?)]))
+    }
+
+    #[tool(description = "Recent files from a plain-English window like \"last hour\", \"past 3 weeks\" or \"since monday\"")]
+    async fn everything_recent_nl(&self, Parameters(p): Parameters<RecentNlReq>) -> Result<CallToolResult, McpError> {
+        let date = filters::recent_phrase(&p.phrase, chrono::Local::now().date_naive()).map_err(|e| McpError::invalid_params(e, None))?;
+        let q = match p.which.as_deref().map(str::to_lowercase).as_deref() {
+            None | Some("modified") => QueryBuilder::new().modified(&date),
+            Some("created") => QueryBuilder::new().created(&date),
+            Some(w) => return Err(McpError::invalid_params(format!("Unknown which '{}' (expected \"modified\" or \"created\")", w), None)),
+        };
+        let q = build(q.ext(p.extension.as_deref().unwrap_or("")))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, false).await?)]))
+    }
+
+    #[tool(description = "Files modified (or created) within a duration of now, e.g. 7d, 3w, 6mo")]
+    async fn everything_newer_than(&self, Parameters(p): Parameters<AgeReq>) -> Result<CallToolResult, McpError> {
+        let q = age_query(&p, ">")?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, false).await?)]))
+    }
+
+    #[tool(description = "Files last modified (or created) longer ago than a duration, e.g. 90d, 6mo, 2y")]
+    async fn everything_older_than(&self, Parameters(p): Parameters<AgeReq>) -> Result<CallToolResult, McpError> {
+        let q = age_query(&p, "<")?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, false).await?)]))
+    }
+
+    #[tool(description = "Search by date created")]
+    async fn everything_search_date_created(&self, Parameters(p): Parameters<DateReq>) -> Result<CallToolResult, McpError> {
+        let q = if p.unchecked.unwrap_or(false) { QueryBuilder::new().func("dc", &p.date_filter) } else { QueryBuilder::new().created(&p.date_filter) };
+        let q = build(q.keywords(p.keywords.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, false).await?)]))
+    }
+
+    #[tool(description = "Search by date modified")]
+    async fn everything_search_date_modified(&self, Parameters(p): Parameters<DateReq>) -> Result<CallToolResult, McpError> {
+        let q = if p.unchecked.unwrap_or(false) { QueryBuilder::new().func("dm", &p.date_filter) } else { QueryBuilder::new().modified(&p.date_filter) };
+        let q = build(q.keywords(p.keywords.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, false).await?)]))
+    }
+
+    #[tool(description = "Search by size")]
+    async fn everything_search_size(&self, Parameters(p): Parameters<SizeReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().size(&p.size_filter).keywords(p.keywords.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, false).await?)]))
+    }
+
+    #[tool(description = "Files between min and max size (inclusive); omit either for an open-ended range")]
+    async fn everything_search_size_range(&self, Parameters(p): Parameters<SizeRangeReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().files_only().size_range(p.min.as_deref(), p.max.as_deref()).keywords(p.keywords.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, false).await?)]))
+    }
+
+    #[tool(description = "Find large files")]
+    async fn everything_search_large(&self, Parameters(p): Parameters<LargeReq>) -> Result<CallToolResult, McpError> {
+        let mut q = QueryBuilder::new().size_gt(p.min_size.as_deref().unwrap_or("100mb"));
+        if let Some(ft) = p.file_type {
+            let ft = ft.to_lowercase();
+            if matches!(ft.as_str(), "video" | "audio" | "archive") { q = q.ext(&category_exts(&ft)); }
+        }
+        let q = build(q)?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, false).await?)]))
+    }
+
+    #[tool(description = "What's using space in a folder: its largest files, biggest first, with a running total and the combined size")]
+    async fn everything_disk_usage(&self, Parameters(p): Parameters<DiskUsageReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().files_only().in_folder(&p.folder).ext(p.ext.as_deref().unwrap_or("")))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        let max = p.max_results.unwrap_or_else(|| default_max(50));
+        let (total, hits) = fetch(&q, &Opts { sort: SORT_SIZE_DESCENDING, ..Opts::max(max) }).await?;
+        if hits.is_empty() { return Ok(CallToolResult::success(vec![Content::text(format!("No files for: {}", q))])); }
+        let mut out = format!("Largest {} of {} file(s) in {}:\n\n{:>10}  {:>10}  path\n", hits.len(), total, p.folder.trim(), "size", "running");
+        let mut running = 0u64;
+        for h in &hits {
+            let size = h.size.unwrap_or(0);
+            running += size;
+            let _ = writeln!(out, "{:>10}  {:>10}  {}", h.size.map_or_else(|| "-".into(), format_size), format_size(running), h.path);
+        }
+        let _ = write!(out, "\nCombined: {} in {} file(s)", format_size(running), hits.len());
+        if (hits.len() as u32) < total { let _ = write!(out, " (the other {} are smaller)", total - hits.len() as u32); }
+        out.push_str(&cap_note(max, total));
+        Ok(CallToolResult::success(vec![Content::text(out)]))
+    }
+
+    #[tool(description = "Find empty folders")]
+    async fn everything_search_empty(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().func("empty", "").keywords(p.keywords.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, p.whole_word.unwrap_or(false), false, p.match_path.unwrap_or(false)).await?)]))
+    }
+
+    #[tool(description = "Search hidden files")]
+    async fn everything_search_hidden(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().func("attrib", "H").keywords(p.keywords.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, p.whole_word.unwrap_or(false), false, p.match_path.unwrap_or(false)).await?)]))
+    }
+
+    #[tool(description = "Search by file attributes, e.g. include system but exclude hidden")]
+    async fn everything_search_attributes(&self, Parameters(p): Parameters<AttrReq>) -> Result<CallToolResult, McpError> {
+        let include = parse_attr_names(p.include.as_deref().unwrap_or("")).map_err(|e| McpError::invalid_params(e, None))?;
+        let exclude = parse_attr_names(p.exclude.as_deref().unwrap_or("")).map_err(|e| McpError::invalid_params(e, None))?;
+        if include.is_empty() && exclude.is_empty() { return Err(McpError::invalid_params("Give at least one attribute in include or exclude", None)); }
+        // attrib:HS needs all listed letters; each excluded letter is its own !attrib: so any one of them rules a file out
+        let mut q = QueryBuilder::new();
+        if !include.is_empty() { q = q.func("attrib", &include); }
+        for c in exclude.chars() { q = q.exclude(&format!("attrib:{}", c)); }
+        let q = build(q.keywords(p.keywords.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        let (total, hits) = fetch(&q, &Opts::max(p.max_results.unwrap_or_else(|| default_max(50)))).await?;
+        if hits.is_empty() { return Ok(CallToolResult::success(vec![Content::text(format!("No results for: {}", q))])); }
+        let mut out = format!("Found {} for {} (showing {}):\n\n", total, q, hits.len());
+        for h in &hits { out.push_str(&format!("{:<8} {}\n", attr_flags_to_string(h.attributes), h.path)); }
+        Ok(CallToolResult::success(vec![Content::text(out)]))
+    }
+
+    #[tool(description = "Search file contents (SLOW)")]
+    async fn everything_search_content(&self, Parameters(p): Parameters<ContentReq>) -> Result<CallToolResult, McpError> {
+        let q = content_query(&p.content, p.folder.as_deref(), p.extensions.as_deref()).map_err(|e| McpError::invalid_params(e, None))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(20)), false, false, false, false).await?)]))
+    }
+
+    #[tool(description = "Content search with the matching lines: each file is listed with its first matching line(s), line numbers \
+        and optional context, like grep -n. Binary files are skipped and reads are capped per file (SLOW)")]
+    async fn everything_content_preview(&self, Parameters(p): Parameters<ContentPreviewReq>) -> Result<CallToolResult, McpError> {
+        let q = content_query(&p.content, p.folder.as_deref(), p.extensions.as_deref()).map_err(|e| McpError::invalid_params(e, None))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        let case = p.match_case.unwrap_or(false);
+        let (total, hits) = fetch(&q, &Opts { max: p.max_results.unwrap_or_else(|| default_max(20)), case, ..Default::default() }).await?;
+        if hits.is_empty() { return Ok(CallToolResult::success(vec![Content::text(format!("No results for: {}", q))])); }
+        let limits = snippet::Limits {
+            lines: p.lines.unwrap_or(1).max(1) as usize, context: p.context.unwrap_or(0) as usize,
+            max_line_len: p.max_line_len.unwrap_or(200).max(20) as usize, max_bytes: p.max_kb_per_file.unwrap_or(1024).max(1).saturating_mul(1024), case,
+        };
+        let paths: Vec<String> = hits.into_iter().filter(|h| !h.is_dir).map(|h| h.path).collect();
+        let needle = p.content.clone();
+        let previews = pipeline::enrich(paths, *pipeline::WORKERS, move |f| { let r = snippet::preview(&f, &needle, limits); (f, r) }).await;
+        let mut out = format!("Found {} (showing {}):\n\n", total, previews.len());
+        for (path, r) in &previews { snippet::format(path, r, &mut out); }
+        Ok(CallToolResult::success(vec![Content::text(out)]))
+    }
+
+    #[tool(description = "Search with regex")]
+    async fn everything_search_regex(&self, Parameters(p): Parameters<RegexReq>) -> Result<CallToolResult, McpError> {
+        // Before the dry run too: a syntax error with its position is what a dry run is for
+        if !p.unchecked.unwrap_or(false) { filters::check_regex(&p.pattern).map_err(|e| McpError::invalid_params(e, None))?; }
+        if let Some(r) = dry_run(&format!("{}  (regex)", p.pattern), p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&p.pattern, p.max_results.unwrap_or_else(|| default_max(50)), false, false, true, false).await?)]))
+    }
+
+    #[tool(description = "Find duplicates by name")]
+    async fn everything_find_duplicates(&self, Parameters(p): Parameters<DupeReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().func("dupe", "").raw(&p.pattern))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        let (total, hits) = fetch(&q, &Opts::max(p.max_results.unwrap_or_else(|| default_max(100)))).await?;
+        let out = match p.format.as_deref().unwrap_or("text") {
+            "text" => format_dupes(&q, total, &hits),
+            "json" => serde_json::json!({ "total": total, "shown": hits.len(), "groups": dupe_groups(&hits) }).to_string(),
+            f => return Err(McpError::invalid_params(format!("Unknown format '{}' (expected \"text\" or \"json\")", f), None)),
+        };
+        Ok(CallToolResult::success(vec![Content::text(out)]))
+    }
+
+    #[tool(description = "Find byte-identical files: matches are grouped by size and only same-size files are hashed (SHA-256). \
+        max_files and max_hash_mb bound the I/O")]
+    async fn everything_find_identical(&self, Parameters(p): Parameters<IdenticalReq>) -> Result<CallToolResult, McpError> {
+        let q = build(QueryBuilder::new().files_only().raw(&p.query))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        let (_, hits) = fetch(&q, &Opts::max(p.max_files.unwrap_or_else(|| default_max(500)))).await?;
+        let files = hits.into_iter().filter_map(|h| h.size.map(|s| (h.path, s))).collect();
+        let (picked, skipped) = identical::candidates(files, p.max_hash_mb.unwrap_or(1024).saturating_mul(1024 * 1024));
+        let hashed = pipeline::enrich(picked, *pipeline::WORKERS, |(f, size)| { let h = identical::sha256_file(&f); (f, size, h) }).await;
+        Ok(CallToolResult::success(vec![Content::text(identical::report(&q, hashed, skipped))]))
+    }
+
+    #[tool(description = "Search with exclusions")]
+    async fn everything_search_exclude(&self, Parameters(p): Parameters<ExcludeReq>) -> Result<CallToolResult, McpError> {
+        let q = p.exclude.split(',').fold(QueryBuilder::new().raw(&p.query), |q, t| q.exclude(t));
+        let q = build(q)?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, false).await?)]))
+    }
+
+    #[tool(description = "Compose include (any of), exclude (none of) and an optional folder: in_folder AND <a | b> AND !c AND !\"dir\\\". \
+        Exclusions that look like paths drop a whole folder subtree")]
+    async fn everything_search_boolean(&self, Parameters(p): Parameters<BooleanReq>) -> Result<CallToolResult, McpError> {
+        fn list(l: &Option<StrList>) -> Vec<&str> {
+            l.as_ref().map(|l| l.items().into_iter().map(str::trim).filter(|s| !s.is_empty()).collect()).unwrap_or_default()
+        }
+        let (include, exclude) = (list(&p.include), list(&p.exclude));
+        if include.is_empty() && exclude.is_empty() && p.in_folder.is_none() {
+            return Err(McpError::invalid_params("give at least one of include, exclude or in_folder", None));
+        }
+        let mut q = QueryBuilder::new();
+        if let Some(f) = p.in_folder.as_deref().filter(|f| !f.trim().is_empty()) { q = q.in_folder(f); }
+        // OR binds tighter than the implicit AND, but the group keeps it obvious and safe with in_folder
+        let q = build(exclude.into_iter().fold(q.or_terms(&include), |q, t| q.exclude(t)))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, false).await?)]))
+    }
+
+    #[tool(description = "Search with OR logic")]
+    async fn everything_search_or(&self, Parameters(p): Parameters<OrReq>) -> Result<CallToolResult, McpError> {
+        let terms: Vec<&str> = p.terms.split(',').collect();
+        let q = build(QueryBuilder::new().or_terms(&terms).keywords(p.and_filter.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, false).await?)]))
+    }
+
+    #[tool(description = "Files modified since the previous call with the same sync_token. Every change is returned, read max_results at a time; the token only advances once a call has read them all. State is kept in server memory per token.")]
+    async fn everything_search_modified_since_last_call(&self, Parameters(p): Parameters<SyncReq>) -> Result<CallToolResult, McpError> {
+        // Whole seconds: dm: compares to the second, and >= takes in changes made in the same second after the stamp
+        let since = |t: &chrono::DateTime<chrono::Utc>| {
+            build(QueryBuilder::new().modified(&format!(">={}", t.with_timezone(&chrono::Local).format("%Y-%m-%dT%H:%M:%S"))).raw(&p.query))
+        };
+        let sync = || SYNC.lock().map_err(|e| SearchError::Lock(e.to_string()));
+        let prev = sync()?.get(&p.sync_token).cloned();
+        let q = match &prev { Some(m) => since(&m.since)?, None => p.query.clone() };
+        // A dry run only peeks and never advances the token
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        // Stamp before querying so files changed while the search runs are picked up next time
+        let now = chrono::SubsecRound::trunc_subsecs(chrono::Utc::now(), 0);
+        if prev.is_none() && !p.initial_full.unwrap_or(false) {
+            sync()?.insert(p.sync_token.clone(), SyncMark { since: now, seen: Default::default() });
+            let r = format!("Sync token '{}' initialized at {}. Next call returns changes since then.", p.sync_token, now.with_timezone(&chrono::Local).format("%Y-%m-%dT%H:%M:%S"));
+            return Ok(CallToolResult::success(vec![Content::text(r)]));
+        }
+        // Every change, a page at a time: stamping after a cut-off page would lose the rest for good
+        let page = p.max_results.unwrap_or(max_cap());
+        let (total, mut hits) = fetch_all(&q, &Opts::max(page), SEARCH_ALL_LIMIT).await?;
+        let complete = hits.len() as u32 >= total;
+        if let Some(m) = &prev { hits.retain(|h| !m.seen.contains(&(h.path.clone(), h.date_modified.clone()))); }
+        for (i, h) in hits.iter_mut().enumerate() { h.index = i as u32; }
+        let mut r = format_hits(&q, hits.len() as u32, 0, &hits);
+        if complete {
+            let stamp = rfc3339(&now);
+            let seen = hits.iter().filter(|h| h.date_modified.as_ref().is_some_and(|t| *t >= stamp)).map(|h| (h.path.clone(), h.date_modified.clone())).collect();
+            sync()?.insert(p.sync_token.clone(), SyncMark { since: now, seen });
+        } else {
+            let _ = write!(r, "\nSync token '{}' not advanced: read {} of {} changes (at most {} per call). Narrow the query.", p.sync_token, hits.len(), total, SEARCH_ALL_LIMIT);
+        }
+        Ok(CallToolResult::success(vec![Content::text(r)]))
+    }
+
+    #[tool(description = "List a folder's immediate children (non-recursive, like ls). Fetches recursively then filters, so a low max_results may hide entries.")]
+    async fn everything_search_by_depth_from_root(&self, Parameters(p): Parameters<ListReq>) -> Result<CallToolResult, McpError> {
+        let base = p.folder_path.trim_end_matches(['\\', '/']);
+        let q = build(QueryBuilder::new().in_folder(base))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        let (total, hits) = fetch(&q, &Opts::max(p.max_results.unwrap_or_else(|| default_max(500)))).await?;
+        // Everything only searches recursively: take the subtree and keep depth-1 entries
+        let fetched = hits.len() as u32;
+        let mut kids: Vec<Hit> = hits.into_iter().filter(|h| depth_below(base, &h.path) == Some(1)).collect();
+        if p.folders_first.unwrap_or(true) { kids.sort_by_key(|h| !h.is_dir); }
+        let mut out = format_hits(&q, kids.len() as u32, 0, &kids);
+        if total > fetched { out.push_str(&format!("\nNote: {} entries under this folder; the listing may be incomplete.\n", total)); }
+        Ok(CallToolResult::success(vec![Content::text(out)]))
     }
 
-    #[tool(description = "Search audio files")]
-    async fn everything_search_audio(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
-        let mut q = "ext:mp3;wav;flac;aac;ogg;wma;m4a".to_string();
-        if let Some(k) = p.keywords.filter(|k| !k.is_empty()) { q.push_str(&format!(" {}", k)); }
-        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or(50), false, false, false, false))]))
+    #[tool(description = "Search and copy matching files into a zip archive, preserving folder structure under a base. Capped by file count and total size. Requires --allow-writes or EVERYTHING_ALLOW_WRITES=1.")]
+    async fn everything_collect(&self, Parameters(p): Parameters<CollectReq>) -> Result<CallToolResult, McpError> {
+        if let Some(r) = dry_run(&p.query, p.dry_run) { return Ok(r); }
+        writes_allowed()?;
+        let (_, hits) = fetch(&p.query, &Opts::max(p.max_results.unwrap_or_else(|| default_max(100)))).await?;
+        let paths: Vec<String> = hits.into_iter().filter(|h| !h.is_dir).map(|h| h.path).collect();
+        // Stat in parallel up front so the size cap is checked without serial round trips
+        let files = pipeline::enrich(paths, *pipeline::WORKERS, |f| { let len = std::fs::metadata(&f).map(|m| m.len()); (f, len) }).await;
+        let r = if files.is_empty() { format!("No files for: {}", p.query) } else {
+            // Up to max_total_mb of reads and compression: off the runtime
+            let (target, overwrite, base) = (p.target_path.clone(), p.overwrite.unwrap_or(false), p.base_folder.clone());
+            let (max_files, max_bytes) = (p.max_files.unwrap_or(100) as usize, p.max_total_mb.unwrap_or(100).saturating_mul(1024 * 1024));
+            tokio::task::spawn_blocking(move || collect::write_zip(&target, overwrite, base.as_deref(), &files, max_files, max_bytes)).await
+                .map_err(|e| McpError::internal_error(format!("Zip writer stopped: {}", e), None))?
+                .map_err(|e| McpError::internal_error(e, None))?
+        };
+        Ok(CallToolResult::success(vec![Content::text(r)]))
     }
 
-    #[tool(description = "Search video files")]
-    async fn everything_search_video(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
-        let mut q = "ext:mp4;avi;mkv;mov;wmv;flv;webm".to_string();
-        if let Some(k) = p.keywords.filter(|k| !k.is_empty()) { q.push_str(&format!(" {}", k)); }
-        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or(50), false, false, false, false))]))
+    #[tool(description = "Flag file names that look like mojibake (UTF-8 shown as Windows-1252, e.g. cafÃ©) with a confidence score and likely original. Heuristic: misses other code pages.")]
+    async fn everything_find_mojibake(&self, Parameters(p): Parameters<MojibakeReq>) -> Result<CallToolResult, McpError> {
+        let q = p.query.filter(|q| !q.is_empty()).unwrap_or_else(|| "regex:[^\\x00-\\x7F]".into());
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        let min = p.min_confidence.unwrap_or(0.5);
+        let (_, hits) = fetch(&q, &Opts::max(p.max_results.unwrap_or_else(|| default_max(500)))).await?;
+        let mut sus: Vec<(f32, String, String)> = hits.into_iter().filter_map(|h| {
+            mojibake::score(&h.name).filter(|(c, _)| *c >= min).map(|(c, why)| (c, h.path, why))
+        }).collect();
+        sus.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let r = if sus.is_empty() { format!("No likely mojibake in results for: {}", q) } else {
+            let mut out = format!("{} suspect name(s):\n\n", sus.len());
+            for (c, path, why) in sus { out.push_str(&format!("[{:.2}] {} - {}\n", c, path, why)); }
+            out
+        };
+        Ok(CallToolResult::success(vec![Content::text(r)]))
     }
 
-    #[tool(description = "Search image files")]
-    async fn everything_search_image(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
-        let mut q = "ext:jpg;jpeg;png;gif;bmp;svg;webp;ico".to_string();
-        if let Some(k) = p.keywords.filter(|k| !k.is_empty()) { q.push_str(&format!(" {}", k)); }
-        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or(50), false, false, false, false))]))
+    #[tool(description = "Search a folder with a glob (**/*.test.ts, src/*.{ts,tsx}). ** recurses; otherwise only that folder level. Shows the Everything query used.")]
+    async fn everything_search_by_glob_in_folder(&self, Parameters(p): Parameters<GlobReq>) -> Result<CallToolResult, McpError> {
+        let q = glob::to_query(&p.folder, &p.glob).map_err(|e| McpError::invalid_params(format!("Invalid glob: {}", e), None))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        let r = search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, false).await?;
+        Ok(CallToolResult::success(vec![Content::text(format!("Query: {}\n\n{}", q, r))]))
     }
 
-    #[tool(description = "Search documents")]
-    async fn everything_search_doc(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
-        let mut q = "ext:pdf;doc;docx;xls;xlsx;ppt;pptx;txt;md".to_string();
-        if let Some(k) = p.keywords.filter(|k| !k.is_empty()) { q.push_str(&format!(" {}", k)); }
-        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or(50), false, false, false, false))]))
+    #[tool(description = "Files matching a shell-style glob anywhere in the index (**/*.rs, src/*.toml, docs/**/*.{md,txt}). Folder segments match whole folder names and * and ? stay within one. Shows the Everything query used.")]
+    async fn everything_search_glob(&self, Parameters(p): Parameters<AnyGlobReq>) -> Result<CallToolResult, McpError> {
+        let (q, path) = glob::glob_to_query(&p.glob).map_err(|e| McpError::invalid_params(format!("Invalid glob: {}", e), None))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        let r = search(&q, p.max_results.unwrap_or_else(|| default_max(50)), false, false, false, path).await?;
+        let how = if path { " (match path)" } else { "" };
+        Ok(CallToolResult::success(vec![Content::text(format!("Query: {}{}\n\n{}", q, how, r))]))
     }
 
-    #[tool(description = "Search code files")]
-    async fn everything_search_code(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
-        let mut q = "ext:cs;py;js;ts;java;cpp;c;h;go;rs;rb;php;ps1".to_string();
-        if let Some(k) = p.keywords.filter(|k| !k.is_empty()) { q.push_str(&format!(" {}", k)); }
-        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or(50), false, false, false, false))]))
+    #[tool(description = "Preview a regex bulk rename: old -> new names for matching files, flagging collisions and invalid names. Never renames anything.")]
+    async fn everything_bulk_rename(&self, Parameters(p): Parameters<RenameReq>) -> Result<CallToolResult, McpError> {
+        let re = regex::Regex::new(&p.pattern).map_err(|e| McpError::invalid_params(format!("Invalid pattern: {}", e), None))?;
+        if let Some(r) = dry_run(&p.query, p.dry_run) { return Ok(r); }
+        let (_, hits) = fetch(&p.query, &Opts::max(p.max_results.unwrap_or_else(|| default_max(100)))).await?;
+        Ok(CallToolResult::success(vec![Content::text(rename::preview(&hits.into_iter().map(|h| h.path).collect::<Vec<_>>(), &re, &p.replacement))]))
     }
 
-    #[tool(description = "Search archives")]
-    async fn everything_search_archive(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
-        let mut q = "ext:zip;rar;7z;tar;gz;bz2;iso".to_string();
-        if let Some(k) = p.keywords.filter(|k| !k.is_empty()) { q.push_str(&format!(" {}", k)); }
-        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or(50), false, false, false, false))]))
+    #[tool(description = "Most frequently opened files (Everything run count), highest first")]
+    async fn everything_most_used(&self, Parameters(p): Parameters<MostUsedReq>) -> Result<CallToolResult, McpError> {
+        let min = p.min_runs.unwrap_or(1).max(1);
+        let q = build(QueryBuilder::new().func("runcount", &format!(">={}", min)).keywords(p.keywords.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        let (total, hits) = fetch(&q, &Opts { max: p.max_results.unwrap_or_else(|| default_max(50)), sort: SORT_RUN_COUNT_DESCENDING, ..Default::default() }).await?;
+        if hits.is_empty() { return Ok(CallToolResult::success(vec![Content::text(format!("No files opened {}+ times (run counts only grow when files are opened through Everything)", min))])); }
+        let mut out = format!("Found {} (showing {}):\n\n", total, hits.len());
+        for h in &hits { out.push_str(&format!("{:>5} runs  {}\n", h.run_count, h.path)); }
+        Ok(CallToolResult::success(vec![Content::text(out)]))
     }
 
-    #[tool(description = "Search executables")]
-    async fn everything_search_exe(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
-        let mut q = "ext:exe;msi;bat;cmd;ps1;sh".to_string();
-        if let Some(k) = p.keywords.filter(|k| !k.is_empty()) { q.push_str(&format!(" {}", k)); }
-        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or(50), false, false, false, false))]))
+    #[tool(description = "Open a file or folder with its default application")]
+    async fn everything_open(&self, Parameters(p): Parameters<OpenReq>) -> Result<CallToolResult, McpError> {
+        let path = resolve_path(&p.target)?;
+        let path = checked_path(&path)?;
+        #[cfg(windows)]
+        let cmd = std::process::Command::new("cmd").args(["/C", "start", ""]).arg(path).spawn();
+        #[cfg(target_os = "macos")]
+        let cmd = std::process::Command::new("open").arg(path).spawn();
+        #[cfg(not(any(windows, target_os = "macos")))]
+        let cmd = std::process::Command::new("xdg-open").arg(path).spawn();
+        cmd.map_err(|e| McpError::internal_error(format!("Open {}: {}", path.display(), e), None))?;
+        let mut r = format!("Opened {}", path.display());
+        // The file is already open: a failed bump is worth a note, not an error
+        if p.mark_used.unwrap_or(false) {
+            let bump = match writes_allowed() {
+                Ok(()) => inc_run_count(&path.to_string_lossy()).await.map_err(|e| e.to_string()),
+                Err(_) => Err("writes disabled".to_string()),
+            };
+            match bump {
+                Ok(n) => { let _ = write!(r, " (run count {})", n); }
+                Err(e) => { let _ = write!(r, " (run count not updated: {})", e); }
+            }
+        }
+        Ok(CallToolResult::success(vec![Content::text(r)]))
     }
 
-    #[tool(description = "Search in folder")]
-    async fn everything_search_in_folder(&self, Parameters(p): Parameters<FolderReq>) -> Result<CallToolResult, McpError> {
-        Ok(CallToolResult::success(vec![Content::text(search(&format!("\"{}\\\" {}", p.folder_path, p.query), p.max_results.unwrap_or(50), false, false, false, false))]))
+    #[tool(description = "Add one to a file's Everything run count so sort_by run_count and \"most used\" rankings reflect it. Returns the new count. \
+        Requires --allow-writes or EVERYTHING_ALLOW_WRITES=1.")]
+    async fn everything_mark_used(&self, Parameters(p): Parameters<PathReq>) -> Result<CallToolResult, McpError> {
+        writes_allowed()?;
+        let path = existing_path(&resolve_path(&p)?)?;
+        let n = inc_run_count(&path).await?;
+        Ok(CallToolResult::success(vec![Content::text(format!("Run count for {}: {}", path, n))]))
     }
 
-    #[tool(description = "Search folders only")]
-    async fn everything_search_folders(&self, Parameters(p): Parameters<FoldersReq>) -> Result<CallToolResult, McpError> {
-        Ok(CallToolResult::success(vec![Content::text(search(&format!("folder: {}", p.query), p.max_results.unwrap_or(50), false, false, false, false))]))
+    #[tool(description = "Read a file's Everything run count (times opened through Everything or marked used)")]
+    async fn everything_get_run_count(&self, Parameters(p): Parameters<PathReq>) -> Result<CallToolResult, McpError> {
+        let path = existing_path(&resolve_path(&p)?)?;
+        Ok(CallToolResult::success(vec![Content::text(format!("Run count for {}: {}", path, get_run_count(&path).await?))]))
     }
 
-    #[tool(description = "Recently modified files")]
-    async fn everything_recent(&self, Parameters(p): Parameters<RecentReq>) -> Result<CallToolResult, McpError> {
-        let mut q = format!("dm:last{}days", p.days.unwrap_or(1));
-        if let Some(ext) = p.extension.filter(|e| !e.is_empty()) { q.push_str(&format!(" ext:{}", ext.trim_start_matches('.'))); }
-        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or(50), false, false, false, false))]))
+    #[tool(description = "Overwrite a file's Everything run count, e.g. to sync usage statistics from other tools. Returns the before and after counts. \
+        Requires --allow-writes or EVERYTHING_ALLOW_WRITES=1.")]
+    async fn everything_set_run_count(&self, Parameters(p): Parameters<SetRunCountReq>) -> Result<CallToolResult, McpError> {
+        writes_allowed()?;
+        let path = existing_path(&resolve_path(&p.target)?)?;
+        let (before, after) = set_run_count(&path, p.run_count).await?;
+        Ok(CallToolResult::success(vec![Content::text(format!("Run count for {}: {} -> {}", path, before, after))]))
     }
 
-    #[tool(description = "Search by date created")]
-    async fn everything_search_date_created(&self, Parameters(p): Parameters<DateReq>) -> Result<CallToolResult, McpError> {
-        let mut q = format!("dc:{}", p.date_filter);
-        if let Some(k) = p.keywords.filter(|k| !k.is_empty()) { q.push_str(&format!(" {}", k)); }
-        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or(50), false, false, false, false))]))
+    #[tool(description = "Show a file or folder selected in its containing folder (Explorer on Windows, Finder on macOS)")]
+    async fn everything_reveal(&self, Parameters(p): Parameters<PathReq>) -> Result<CallToolResult, McpError> {
+        let path = resolve_path(&p)?;
+        let path = checked_path(&path)?;
+        #[cfg(windows)]
+        let cmd = {
+            use std::os::windows::process::CommandExt;
+            // Explorer wants /select,"path" as one raw argument; checked_path already refused quotes
+            std::process::Command::new("explorer").raw_arg(format!("/select,\"{}\"", path.display())).spawn()
+        };
+        #[cfg(target_os = "macos")]
+        let cmd = std::process::Command::new("open").arg("-R").arg(path).spawn();
+        // No portable way to select an item, so open the parent folder
+        #[cfg(not(any(windows, target_os = "macos")))]
+        let cmd = std::process::Command::new("xdg-open").arg(path.parent().unwrap_or(path)).spawn();
+        cmd.map_err(|e| McpError::internal_error(format!("Reveal {}: {}", path.display(), e), None))?;
+        Ok(CallToolResult::success(vec![Content::text(format!("Revealed {}", path.display()))]))
     }
 
-    #[tool(description = "Search by date modified")]
-    async fn everything_search_date_modified(&self, Parameters(p): Parameters<DateReq>) -> Result<CallToolResult, McpError> {
-        let mut q = format!("dm:{}", p.date_filter);
-        if let Some(k) = p.keywords.filter(|k| !k.is_empty()) { q.push_str(&format!(" {}", k)); }
-        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or(50), false, false, false, false))]))
+    #[tool(description = "Copy a path to the Windows clipboard")]
+    async fn everything_copy_path(&self, Parameters(p): Parameters<PathReq>) -> Result<CallToolResult, McpError> {
+        let path = resolve_path(&p)?;
+        let path = path.trim();
+        if path.is_empty() { return Err(McpError::invalid_params("path is empty", None)); }
+        #[cfg(all(windows, feature = "clipboard"))]
+        {
+            clipboard_win::set_clipboard_string(path).map_err(|e| McpError::internal_error(format!("Clipboard: {}", e), None))?;
+            Ok(CallToolResult::success(vec![Content::text(format!("Copied {}", path))]))
+        }
+        #[cfg(not(all(windows, feature = "clipboard")))]
+        Err(McpError::internal_error("Clipboard support not built in (needs Windows and the clipboard feature)", None))
     }
 
-    #[tool(description = "Search by size")]
-    async fn everything_search_size(&self, Parameters(p): Parameters<SizeReq>) -> Result<CallToolResult, McpError> {
-        let mut q = format!("size:{}", p.size_filter);
-        if let Some(k) = p.keywords.filter(|k| !k.is_empty()) { q.push_str(&format!(" {}", k)); }
-        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or(50), false, false, false, false))]))
+    #[tool(description = "Files not accessed in a long time, least recently accessed first")]
+    async fn everything_stale(&self, Parameters(p): Parameters<StaleReq>) -> Result<CallToolResult, McpError> {
+        let days = p.min_age_days.unwrap_or(365);
+        let cutoff = chrono::Local::now().date_naive() - chrono::Duration::days(days as i64);
+        let q = build(QueryBuilder::new().files_only().func("da", &format!("<{}", cutoff.format("%Y-%m-%d"))).keywords(p.keywords.as_deref()))?;
+        if let Some(r) = dry_run(&q, p.dry_run) { return Ok(r); }
+        let o = Opts { max: p.max_results.unwrap_or_else(|| default_max(50)), sort: SORT_DATE_ACCESSED_ASCENDING, accessed: true, ..Default::default() };
+        let (total, hits) = fetch(&q, &o).await?;
+        if hits.is_empty() { return Ok(CallToolResult::success(vec![Content::text(format!("No files last accessed before {}", cutoff))])); }
+        let mut out = format!("Found {} not accessed in {}+ days (showing {}):\n\n", total, days, hits.len());
+        for h in &hits {
+            let da = h.date_accessed.as_deref().map_or_else(|| "unknown".into(), short_time);
+            out.push_str(&format!("{}  {}\n", da, h.path));
+        }
+        if hits.iter().all(|h| h.date_accessed.is_none()) {
+            out.push_str("\nWarning: no result has an access time. Last-access tracking may be disabled on these volumes \
+                (fsutil behavior query disablelastaccess), or Everything isn't indexing Date Accessed.\n");
+        }
+        Ok(CallToolResult::success(vec![Content::text(out)]))
     }
 
-    #[tool(description = "Find large files")]
-    async fn everything_search_large(&self, Parameters(p): Parameters<LargeReq>) -> Result<CallToolResult, McpError> {
-        let mut q = format!("size:>{}", p.min_size.as_deref().unwrap_or("100mb"));
-        if let Some(ft) = p.file_type {
-            q.push_str(match ft.to_lowercase().as_str() {
-                "video" => " ext:mp4;avi;mkv;mov",
-                "audio" => " ext:mp3;wav;flac",
-                "archive" => " ext:zip;rar;7z;iso",
-                _ => ""
-            });
+    #[tool(description = "Count matching files per extension, most common first")]
+    async fn everything_group_by_ext(&self, Parameters(p): Parameters<GroupReq>) -> Result<CallToolResult, McpError> {
+        if let Some(r) = dry_run(&p.query, p.dry_run) { return Ok(r); }
+        let limit = p.max_results.unwrap_or(1000).clamp(1, 10_000);
+        let (mut counts, mut folders, mut seen, mut total) = (HashMap::<String, u32>::new(), 0u32, 0u32, 0u32);
+        // fetch returns at most 500 per call, so page through up to the limit
+        while seen < limit {
+            let (tot, hits) = fetch(&p.query, &Opts { max: (limit - seen).min(500), offset: seen, ..Default::default() }).await?;
+            total = tot;
+            if hits.is_empty() { break; }
+            seen += hits.len() as u32;
+            for h in hits {
+                if h.is_dir { folders += 1; continue; }
+                *counts.entry(h.extension.map(|e| e.to_lowercase()).unwrap_or_else(|| "(none)".into())).or_default() += 1;
+            }
         }
-        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or(50), false, false, false, false))]))
+        if seen == 0 { return Ok(CallToolResult::success(vec![Content::text(format!("No results for: {}", p.query))])); }
+        let mut rows: Vec<(String, u32)> = counts.into_iter().collect();
+        rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let mut out = format!("Extensions for '{}' ({} of {} matches tallied):\n\n", p.query, seen, total);
+        for (ext, n) in &rows { out.push_str(&format!("{:>7}  {}\n", n, ext)); }
+        if folders > 0 { out.push_str(&format!("{:>7}  (folders, not counted)\n", folders)); }
+        Ok(CallToolResult::success(vec![Content::text(out)]))
     }
 
-    #[tool(description = "Find empty folders")]
-    async fn everything_search_empty(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
-        let q = p.keywords.filter(|k| !k.is_empty()).map(|k| format!("empty: {}", k)).unwrap_or("empty:".into());
-        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or(50), false, false, false, false))]))
+    #[tool(description = "Explain an Everything query: operators, functions, wildcards, and likely mistakes. Does not run it.")]
+    async fn everything_explain(&self, Parameters(p): Parameters<ExplainReq>) -> Result<CallToolResult, McpError> {
+        Ok(CallToolResult::success(vec![Content::text(explain::explain(&p.query))]))
     }
+}
 
-    #[tool(description = "Search hidden files")]
-    async fn everything_search_hidden(&self, Parameters(p): Parameters<KeyReq>) -> Result<CallToolResult, McpError> {
-        let mut q = "attrib:H".to_string();
-        if let Some(k) = p.keywords.filter(|k| !k.is_empty()) { q.push_str(&format!(" {}", k)); }
-        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or(50), false, false, false, false))]))
+// call_tool and list_tools are written out instead of using #[tool_handler] so each call gets a span
+impl ServerHandler for EvMcp {
+    async fn call_tool(&self, request: CallToolRequestParam, context: RequestContext<RoleServer>) -> Result<CallToolResult, McpError> {
+        let span = tracing::info_span!("tool", name = %request.name);
+        let start = std::time::Instant::now();
+        let r = self.tool_router.call(ToolCallContext::new(self, request, context)).instrument(span.clone()).await;
+        let _g = span.enter();
+        let elapsed_ms = start.elapsed().as_millis() as u64;
+        match &r {
+            Ok(res) if res.is_error == Some(true) => tracing::info!(elapsed_ms, "tool returned an error result"),
+            Ok(_) => tracing::info!(elapsed_ms, "tool done"),
+            Err(e) => tracing::warn!(elapsed_ms, error = %e.message, "tool failed"),
+        }
+        r
     }
 
-    #[tool(description = "Search file contents (SLOW)")]
-    async fn everything_search_content(&self, Parameters(p): Parameters<ContentReq>) -> Result<CallToolResult, McpError> {
-        let mut q = String::new();
-        if let Some(f) = p.folder.filter(|f| !f.is_empty()) { q.push_str(&format!("\"{}\\\" ", f)); }
-        if let Some(e) = p.extensions.filter(|e| !e.is_empty()) { q.push_str(&format!("ext:{} ", e.replace(',', ";"))); }
-        q.push_str(&format!("content:\"{}\"", p.content));
-        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or(20), false, false, false, false))]))
+    async fn list_tools(&self, _: Option<PaginatedRequestParam>, _: RequestContext<RoleServer>) -> Result<ListToolsResult, McpError> {
+        Ok(ListToolsResult::with_all_items(self.tool_router.list_all()))
     }
 
-    #[tool(description = "Search with regex")]
-    async fn everything_search_regex(&self, Parameters(p): Parameters<RegexReq>) -> Result<CallToolResult, McpError> {
-        Ok(CallToolResult::success(vec![Content::text(search(&p.pattern, p.max_results.unwrap_or(50), false, false, true, false))]))
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            protocol_version: ProtocolVersion::V_2024_11_05,
+            capabilities: ServerCapabilities::builder().enable_tools().enable_resources().enable_prompts().build(),
+            server_info: server_info(),
+            instructions: Some("Everything Search MCP (Rust) - 64 tools".into()),
+        }
     }
 
-    #[tool(description = "Find duplicates by name")]
-    async fn everything_find_duplicates(&self, Parameters(p): Parameters<DupeReq>) -> Result<CallToolResult, McpError> {
-        Ok(CallToolResult::success(vec![Content::text(search(&format!("dupe: {}", p.pattern), p.max_results.unwrap_or(100), false, false, false, false))]))
+    async fn list_prompts(&self, _: Option<PaginatedRequestParam>, _: RequestContext<RoleServer>) -> Result<ListPromptsResult, McpError> {
+        let arg = |name: &str, description: &str, required: bool| PromptArgument {
+            name: name.into(), title: None, description: Some(description.into()), required: Some(required),
+        };
+        Ok(ListPromptsResult::with_all_items(vec![Prompt::new(QUERY_PROMPT_NAME, Some("Build an Everything search query step by step, with a syntax cheat sheet"), Some(vec![
+            arg("goal", "What you are looking for, in plain words", true),
+            arg("folder", "Folder to search in", false),
+            arg("types", "File types or extensions, e.g. pdf, images", false),
+            arg("age", "When it was modified, e.g. this week, before 2023", false),
+            arg("size", "Size, e.g. over 100 MB", false),
+        ]))]))
     }
 
-    #[tool(description = "Search with exclusions")]
-    async fn everything_search_exclude(&self, Parameters(p): Parameters<ExcludeReq>) -> Result<CallToolResult, McpError> {
-        let ex: Vec<String> = p.exclude.split(',').map(|s| format!("!{}", s.trim())).collect();
-        Ok(CallToolResult::success(vec![Content::text(search(&format!("{} {}", p.query, ex.join(" ")), p.max_results.unwrap_or(50), false, false, false, false))]))
+    async fn get_prompt(&self, p: GetPromptRequestParam, _: RequestContext<RoleServer>) -> Result<GetPromptResult, McpError> {
+        if p.name != QUERY_PROMPT_NAME { return Err(McpError::invalid_params(format!("Unknown prompt {}", p.name), None)); }
+        Ok(GetPromptResult {
+            description: Some("Build an Everything search query".into()),
+            messages: vec![PromptMessage::new_text(PromptMessageRole::User, query_prompt(p.arguments.as_ref()))],
+        })
     }
 
-    #[tool(description = "Search with OR logic")]
-    async fn everything_search_or(&self, Parameters(p): Parameters<OrReq>) -> Result<CallToolResult, McpError> {
-        let oq = p.terms.split(',').map(|s| s.trim()).collect::<Vec<_>>().join(" | ");
-        let q = p.and_filter.filter(|f| !f.is_empty()).map(|f| format!("({}) {}", oq, f)).unwrap_or(oq);
-        Ok(CallToolResult::success(vec![Content::text(search(&q, p.max_results.unwrap_or(50), false, false, false, false))]))
+    async fn list_resources(&self, _: Option<PaginatedRequestParam>, _: RequestContext<RoleServer>) -> Result<ListResourcesResult, McpError> {
+        let mut r = RawResource::new(CATEGORIES_URI, "categories");
+        r.description = Some("Extension lists used by the category search tools, as {category: [extensions]}".into());
+        r.mime_type = Some("application/json".into());
+        Ok(ListResourcesResult::with_all_items(vec![r.no_annotation()]))
     }
-}
 
-#[tool_handler]
-impl ServerHandler for EvMcp {
-    fn get_info(&self) -> ServerInfo {
-        ServerInfo {
-            protocol_version: ProtocolVersion::V_2024_11_05,
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
-            server_info: Implementation::from_build_env(),
-            instructions: Some("Everything Search MCP (Rust) - 24 tools".into()),
-        }
+    async fn read_resource(&self, p: ReadResourceRequestParam, _: RequestContext<RoleServer>) -> Result<ReadResourceResult, McpError> {
+        if p.uri != CATEGORIES_URI { return Err(McpError::resource_not_found(format!("Unknown resource {}", p.uri), None)); }
+        let map: serde_json::Map<String, serde_json::Value> = CATEGORIES.as_ref().into_iter().flatten()
+            .map(|(n, e)| (n.clone(), serde_json::json!(e))).collect();
+        let text = serde_json::to_string_pretty(&map).map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        Ok(ReadResourceResult { contents: vec![ResourceContents::TextResourceContents { uri: p.uri, mime_type: Some("application/json".into()), text, meta: None }] })
     }
 }
 
@@ -346,6 +2290,47 @@ impl ServerHandler for EvMcp {
 #[command(name = "everything")]
 #[command(about = "Everything Search - CLI + MCP modes")]
 struct Cli {
+    /// Path to Everything64.dll (overrides EVERYTHING_DLL_PATH)
+    #[arg(long, global = true)]
+    dll_path: Option<String>,
+    /// Seconds to reuse identical MCP search results; 0 disables (overrides EVERYTHING_CACHE_TTL)
+    #[arg(long, global = true)]
+    cache_ttl: Option<u64>,
+    /// TOML or JSON file with category extension lists merged over the defaults (overrides EVERYTHING_CATEGORIES)
+    #[arg(long, global = true)]
+    categories: Option<String>,
+    /// Retries when Everything's IPC is unavailable, e.g. mid-restart; 0 fails at once (overrides EVERYTHING_IPC_RETRIES)
+    #[arg(long, global = true)]
+    ipc_retries: Option<u32>,
+    /// JSON file holding saved searches (overrides EVERYTHING_SAVED_SEARCHES; default: saved-searches.json in the config folder)
+    #[arg(long, global = true)]
+    saved_searches: Option<String>,
+    /// Most results any search returns, at most 500 (overrides EVERYTHING_MAX_RESULTS)
+    #[arg(long, global = true)]
+    max_cap: Option<u32>,
+    /// Results per search when a request omits max_results, 1-500 (overrides EVERYTHING_DEFAULT_MAX)
+    #[arg(long, global = true)]
+    default_max: Option<u32>,
+    /// Layout of each text result line, e.g. "{size}\t{path}". Placeholders: path, name, dir, ext, size, bytes,
+    /// modified, created, accessed, attr, kind, index; {{ and }} are literal braces
+    #[arg(long, global = true, value_parser = template::Template::parse)]
+    template: Option<template::Template>,
+    /// Print only result lines, without the "Found N" header, for piping into other tools
+    #[arg(short = 'q', long, global = true)]
+    quiet: bool,
+    /// Print JSON instead of text: results as {total, shown, offset, results}, count as {"count": N}, and status,
+    /// version and doctor as objects (ignored by completions, reload and mcp)
+    #[arg(long, global = true)]
+    json: bool,
+    /// Show sizes in 1000-based units (1 KB = 1000 bytes) instead of 1024-based (same as EVERYTHING_SI_SIZES=1)
+    #[arg(long, global = true)]
+    si: bool,
+    /// Enable tools that write files or change Everything's data (same as EVERYTHING_ALLOW_WRITES=1)
+    #[arg(long, global = true)]
+    allow_writes: bool,
+    /// Debug logging to stderr (ignored when RUST_LOG is set)
+    #[arg(short = 'v', long, global = true)]
+    verbose: bool,
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -365,6 +2350,24 @@ enum Commands {
         /// Use regex
         #[arg(short = 'r', long)]
         regex: bool,
+        /// Write results to a file instead of stdout
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+        /// Output format: txt, csv, json, ndjson (default: from --output extension, else txt). Without --output,
+        /// prints that format to stdout
+        #[arg(short = 'f', long)]
+        format: Option<String>,
+    },
+    /// Count matches without listing them
+    Count {
+        /// Search query
+        query: String,
+        /// Match case
+        #[arg(short = 'c', long)]
+        case: bool,
+        /// Use regex
+        #[arg(short = 'r', long)]
+        regex: bool,
     },
     /// Search by extension
     Ext {
@@ -387,6 +2390,46 @@ enum Commands {
         #[arg(short = 'n', long, default_value = "20")]
         max: u32,
     },
+    /// Search file contents (slow: reads every candidate file)
+    Content {
+        /// Text to find
+        text: String,
+        /// Extensions (comma-separated)
+        #[arg(short = 'e', long)]
+        ext: Option<String>,
+        /// Only search below this folder
+        #[arg(short = 'f', long)]
+        folder: Option<String>,
+        #[arg(short = 'n', long, default_value = "20")]
+        max: u32,
+    },
+    /// Files that share a name with another file (dupe:)
+    Duplicates {
+        /// Name pattern, e.g. *.jpg
+        pattern: String,
+        #[arg(short = 'n', long, default_value = "20")]
+        max: u32,
+        /// Flat list instead of one group per name
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Search with a regular expression
+    Regex {
+        /// Regex pattern
+        pattern: String,
+        #[arg(short = 'n', long, default_value = "20")]
+        max: u32,
+        /// Send the pattern without the syntax pre-check
+        #[arg(long)]
+        unchecked: bool,
+    },
+    /// Search for folders only
+    Folders {
+        /// Search query
+        query: String,
+        #[arg(short = 'n', long, default_value = "20")]
+        max: u32,
+    },
     /// Large files
     Large {
         /// Min size (e.g. 100mb)
@@ -397,27 +2440,83 @@ enum Commands {
     },
     /// Check Everything status
     Status,
+    /// Check that Everything is installed, running and answering, with a fix for each problem found
+    Doctor,
+    /// Print a shell completion script, e.g. `everything-mcp-rs completions bash > /etc/bash_completion.d/everything-mcp-rs`
+    Completions {
+        /// bash, zsh, fish, powershell or elvish
+        shell: clap_complete::Shell,
+    },
+    /// Print this server's version and, if available, Everything's
+    Version,
+    /// Load Everything64.dll again and report the result
+    Reload,
     /// Run as MCP server (default if no args)
     Mcp,
 }
 
-fn cli_status() {
-    match DLL.lock() {
-        Ok(g) => match g.as_ref() {
-            Some(dll) => unsafe {
-                if (dll.is_loaded)() != 0 {
-                    println!("Everything v{}.{}.{}.{} - Ready",
-                        (dll.get_ver[0])(), (dll.get_ver[1])(), (dll.get_ver[2])(), (dll.get_ver[3])());
-                } else {
-                    eprintln!("Everything not available. Is it running?");
-                    std::process::exit(1);
-                }
-            },
-            None => {
-                eprintln!("Everything64.dll not loaded");
-                std::process::exit(1);
-            }
-        },
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) { format!("\"{}\"", s.replace('"', "\"\"")) } else { s.to_string() }
+}
+
+/// Write a result set to `path` as txt, csv or json; the format falls back to the file extension
+fn write_results(path: &str, format: Option<&str>, q: &str, total: u32, hits: &[Hit]) -> Result<(), String> {
+    let ext = std::path::Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    let body = match format {
+        Some(f) => render_results(f, q, total, hits)?,
+        None => render_results(&ext, q, total, hits).unwrap_or_else(|_| format_hits(q, total, 0, hits)),
+    };
+    std::fs::write(path, body).map_err(|e| format!("Write {}: {}", path, e))
+}
+
+/// A result set as txt, csv, json or ndjson
+fn render_results(format: &str, q: &str, total: u32, hits: &[Hit]) -> Result<String, String> {
+    Ok(match format {
+        "csv" => format_csv(hits),
+        "json" => serde_json::to_string_pretty(&json_page(total, 0, hits)).map_err(|e| format!("JSON: {}", e))?,
+        "ndjson" | "jsonl" => ndjson(json_page(total, 0, hits)),
+        "txt" | "text" | "" => format_hits(q, total, 0, hits),
+        f => return Err(format!("Unknown format '{}' (expected txt, csv, json or ndjson)", f)),
+    })
+}
+
+/// CSV with a header row, quoted per RFC 4180 (fields with commas, quotes or line breaks), so it loads with
+/// PowerShell's ConvertFrom-Csv. Sizes are bytes, dates RFC 3339 UTC, attributes letters as in text output
+fn format_csv(hits: &[Hit]) -> String {
+    let mut out = String::from("path,name,extension,is_dir,size,date_modified,date_created,attributes\n");
+    let time = |t: &Option<String>| t.clone().unwrap_or_default();
+    for h in hits {
+        let _ = writeln!(out, "{},{},{},{},{},{},{},{}", csv_field(&h.path), csv_field(&h.name), csv_field(h.extension.as_deref().unwrap_or("")),
+            h.is_dir, h.size.map(|n| n.to_string()).unwrap_or_default(), time(&h.date_modified), time(&h.date_created), attr_flags_to_string(h.attributes));
+    }
+    out
+}
+
+/// The built query, or the invalid value reported on stderr with exit code 1
+fn cli_build(q: QueryBuilder) -> String {
+    q.build().unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    })
+}
+
+/// Print a search result, or report the failure on stderr and exit nonzero
+fn cli_print(r: Result<String, SearchError>) {
+    match r {
+        // Quiet output is bare lines, each already newline-terminated, so `| wc -l` counts results
+        Ok(out) if quiet() => print!("{}", out),
+        Ok(out) => println!("{}", out),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// --json output: pretty, so it reads in a terminal and still parses with jq or ConvertFrom-Json
+fn cli_print_json(r: Result<serde_json::Value, SearchError>) {
+    match r {
+        Ok(v) => println!("{:#}", v),
         Err(e) => {
             eprintln!("Error: {}", e);
             std::process::exit(1);
@@ -425,37 +2524,620 @@ fn cli_status() {
     }
 }
 
+/// Run a subcommand's search and print it as text, or with --json as a json_page envelope
+async fn cli_search(q: &str, o: &Opts, json: bool) {
+    if json {
+        cli_print_json(fetch(q, o).await.map(|(total, hits)| json_page(total, 0, &hits)));
+    } else {
+        cli_print(fetch(q, o).await.map(|(total, hits)| format_hits(q, total, 0, &hits) + &cap_note(o.max, total)));
+    }
+}
+
+/// {backend, major, revision, build, is_db_loaded, error?} for everything_version and `version --json`
+async fn version_json() -> serde_json::Value {
+    let mut v = serde_json::json!({
+        "backend": BACKEND.name(), "major": null, "minor": null, "revision": null, "build": null, "is_db_loaded": false,
+    });
+    if BACKEND.name() != "Everything" {
+        v["error"] = "not using the Everything backend".into();
+    } else {
+        let base = v.clone();
+        v = worker::call(move |g| {
+            match g.as_ref() {
+                Ok(dll) => unsafe {
+                    let ver: Vec<u32> = dll.get_ver.iter().map(|f| f()).collect();
+                    // All zero when Everything isn't running
+                    if ver[0] > 0 {
+                        for (k, n) in ["major", "minor", "revision", "build"].iter().zip(ver) { v[*k] = n.into(); }
+                    } else {
+                        v["error"] = "Everything is not running".into();
+                    }
+                    v["is_db_loaded"] = ((dll.is_loaded)() != 0).into();
+                },
+                Err(e) => v["error"] = format!("DLL not loaded: {}", e).into(),
+            }
+            v
+        }).await.unwrap_or_else(|e| {
+            let mut v = base;
+            v["error"] = e.to_string().into();
+            v
+        });
+    }
+    v
+}
+
+/// This server's name and version. Implementation::from_build_env() is expanded inside rmcp, so it reports
+/// rmcp's own crate name and version instead
+fn server_info() -> Implementation {
+    Implementation { name: env!("CARGO_PKG_NAME").into(), version: env!("CARGO_PKG_VERSION").into(), ..Implementation::from_build_env() }
+}
+
+/// Server version, then the Everything version when the DLL is loaded and Everything is running.
+/// Never fails: a missing DLL or stopped service is reported, not an error
+async fn cli_version(json: bool) {
+    let info = server_info();
+    if json {
+        let mut v = version_json().await;
+        v["name"] = info.name.into();
+        v["version"] = info.version.into();
+        println!("{:#}", v);
+        return;
+    }
+    println!("{} {}", info.name, info.version);
+    if BACKEND.name() != "Everything" {
+        println!("Everything: not used (backend: {})", BACKEND.name());
+        return;
+    }
+    let line = worker::call(|g| match g.as_ref() {
+        Ok(dll) => unsafe {
+            let ver: Vec<u32> = dll.get_ver.iter().map(|f| f()).collect();
+            // All zero when Everything isn't running
+            if ver[0] > 0 { format!("Everything: {}.{}.{}.{}", ver[0], ver[1], ver[2], ver[3]) } else { "Everything: not running".into() }
+        },
+        Err(e) => format!("Everything: DLL not loaded ({})", e),
+    }).await.unwrap_or_else(|e| format!("Everything: {}", e));
+    println!("{}", line);
+}
+
+/// Exits 1 when the backend isn't ready; --json prints {backend, ready, detail} either way
+async fn cli_status(json: bool) {
+    let (ready, detail) = if BACKEND.name() != "Everything" {
+        match BACKEND.status() {
+            Ok(r) => (true, format!("{} fallback: {}", BACKEND.name(), r)),
+            Err(e) => (false, format!("{} fallback not available: {}", BACKEND.name(), e)),
+        }
+    } else {
+        worker::call(|g| match g.as_ref() {
+            Ok(dll) => unsafe {
+                if (dll.is_loaded)() != 0 {
+                    (true, format!("Everything v{}.{}.{}.{} - Ready",
+                        (dll.get_ver[0])(), (dll.get_ver[1])(), (dll.get_ver[2])(), (dll.get_ver[3])()))
+                } else {
+                    (false, "Everything not available. Is it running?".to_string())
+                }
+            },
+            Err(e) => (false, format!("Everything64.dll not loaded: {}", e)),
+        }).await.unwrap_or_else(|e| (false, e.to_string()))
+    };
+    if json {
+        println!("{:#}", serde_json::json!({ "backend": BACKEND.name(), "ready": ready, "detail": detail }));
+    } else if ready {
+        println!("{}", detail);
+    } else {
+        eprintln!("{}", detail);
+    }
+    if !ready { std::process::exit(1); }
+}
+
+/// Resolves on Ctrl-C, or on Windows also when the console window is closed; names what arrived
+async fn shutdown_signal() -> &'static str {
+    #[cfg(windows)]
+    if let Ok(mut close) = tokio::signal::windows::ctrl_close() {
+        return tokio::select! {
+            _ = tokio::signal::ctrl_c() => "Ctrl-C",
+            _ = close.recv() => "console closed",
+        };
+    }
+    match tokio::signal::ctrl_c().await {
+        Ok(()) => "Ctrl-C",
+        // No signal handling available: never resolve, the transport closing still ends the server
+        Err(_) => std::future::pending().await,
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    // stderr only: stdout carries the MCP protocol
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(if cli.verbose { "everything_mcp_rs=debug,rmcp=info" } else { "warn" }));
+    tracing_subscriber::fmt().with_env_filter(filter).with_writer(std::io::stderr).with_ansi(false).init();
+    if let Some(p) = cli.dll_path { let _ = DLL_PATH.set(p); }
+    if let Some(t) = cli.cache_ttl { let _ = CACHE_TTL.set(t); }
+    if let Some(m) = cli.max_cap { let _ = MAX_CAP.set(m); }
+    if let Some(m) = cli.default_max { let _ = DEFAULT_MAX.set(m); }
+    if let Some(r) = cli.ipc_retries { let _ = IPC_RETRIES.set(r); }
+    if let Some(t) = cli.template { let _ = TEMPLATE.set(t); }
+    let _ = QUIET.set(cli.quiet);
+    let _ = SI_SIZES.set(cli.si);
+    let _ = ALLOW_WRITES.set(cli.allow_writes);
+    if let Some(c) = cli.categories { let _ = CATEGORIES_PATH.set(c); }
+    if let Some(p) = cli.saved_searches { let _ = SAVED_PATH.set(p); }
+    if let Err(e) = &*CATEGORIES { anyhow::bail!("Category config: {}", e); }
 
+    let json = cli.json;
     match cli.command {
-        Some(Commands::Search { query, max, case, regex }) => {
-            println!("{}", search(&query, max, case, false, regex, false));
+        Some(Commands::Search { query, max, case, regex, output: None, format: None }) => {
+            cli_search(&query, &Opts { max, case, regex, ..Default::default() }, json).await;
+        }
+        Some(Commands::Search { query, max, case, regex, output: None, format: Some(f) }) => {
+            let out = fetch(&query, &Opts { max, case, regex, created: f == "csv", ..Default::default() }).await.map_err(|e| e.to_string())
+                .and_then(|(total, hits)| render_results(&f, &query, total, &hits));
+            match out {
+                Ok(out) => print!("{}", out),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Commands::Search { query, max, case, regex, output: Some(path), format }) => {
+            let csv = format.as_deref().map_or(path.to_lowercase().ends_with(".csv"), |f| f == "csv");
+            let written = fetch(&query, &Opts { max, case, regex, created: csv, ..Default::default() }).await.map_err(|e| e.to_string())
+                .and_then(|(total, hits)| write_results(&path, format.as_deref(), &query, total, &hits).map(|_| hits.len()));
+            match written {
+                Ok(n) if json => println!("{:#}", serde_json::json!({ "written": n, "path": path })),
+                Ok(n) => println!("Wrote {} results to {}", n, path),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Commands::Count { query, case, regex }) => {
+            match count(&query, &Opts { case, regex, ..Default::default() }).await {
+                Ok(n) if json => println!("{}", serde_json::json!({ "count": n })),
+                Ok(n) => println!("{}", n),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
         }
         Some(Commands::Ext { extensions, keywords, max }) => {
-            let eq: String = extensions.split(',').map(|e| format!("ext:{}", e.trim().trim_start_matches('.'))).collect::<Vec<_>>().join(" | ");
-            let q = keywords.filter(|k| !k.is_empty()).map(|k| format!("({}) {}", eq, k)).unwrap_or(eq);
-            println!("{}", search(&q, max, false, false, false, false));
+            let q = cli_build(QueryBuilder::new().ext(&extensions).keywords(keywords.as_deref()));
+            cli_search(&q, &Opts::max(max), json).await;
         }
         Some(Commands::Recent { days, ext, max }) => {
-            let mut q = format!("dm:last{}days", days);
-            if let Some(e) = ext.filter(|e| !e.is_empty()) { q.push_str(&format!(" ext:{}", e.trim_start_matches('.'))); }
-            println!("{}", search(&q, max, false, false, false, false));
+            let q = cli_build(QueryBuilder::new().modified(&format!("last{}days", days)).ext(ext.as_deref().unwrap_or("")));
+            cli_search(&q, &Opts::max(max), json).await;
+        }
+        Some(Commands::Content { text, ext, folder, max }) => {
+            match content_query(&text, folder.as_deref(), ext.as_deref()) {
+                Ok(q) => {
+                    eprintln!("Searching file contents; this reads every candidate file and can take a while. Narrow with --ext/--folder.");
+                    cli_search(&q, &Opts::max(max), json).await;
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Commands::Duplicates { pattern, max, raw }) => {
+            let q = cli_build(QueryBuilder::new().func("dupe", "").raw(&pattern));
+            if raw {
+                cli_search(&q, &Opts::max(max), json).await;
+            } else if json {
+                cli_print_json(fetch(&q, &Opts::max(max)).await
+                    .map(|(total, hits)| serde_json::json!({ "total": total, "shown": hits.len(), "groups": dupe_groups(&hits) })));
+            } else {
+                cli_print(fetch(&q, &Opts::max(max)).await.map(|(total, hits)| format_dupes(&q, total, &hits)));
+            }
+        }
+        Some(Commands::Regex { pattern, max, unchecked }) => {
+            if let Err(e) = filters::check_regex(&pattern).map_err(|e| e.replace("unchecked: true", "--unchecked")) {
+                if !unchecked { eprintln!("Error: {}", e); std::process::exit(1); }
+            }
+            cli_search(&pattern, &Opts { max, regex: true, ..Default::default() }, json).await;
+        }
+        Some(Commands::Folders { query, max }) => {
+            cli_search(&cli_build(QueryBuilder::new().folders_only().raw(&query)), &Opts::max(max), json).await;
         }
         Some(Commands::Large { size, max }) => {
-            println!("{}", search(&format!("size:>{}", size), max, false, false, false, false));
+            match filters::parse_size(&size) {
+                Ok(size) => cli_search(&format!("size:>{}", size), &Opts::max(max), json).await,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
         }
         Some(Commands::Status) => {
-            cli_status();
+            cli_status(json).await;
+        }
+        Some(Commands::Version) => cli_version(json).await,
+        Some(Commands::Completions { shell }) => {
+            clap_complete::generate(shell, &mut Cli::command(), env!("CARGO_PKG_NAME"), &mut std::io::stdout());
         }
+        Some(Commands::Doctor) => {
+            let (ok, report) = if json { doctor::run_json().await } else { doctor::run().await };
+            println!("{}", report);
+            if !ok { std::process::exit(1); }
+        }
+        Some(Commands::Reload) => match reload_dll().await {
+            Ok(r) => println!("{}", r),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
         Some(Commands::Mcp) | None => {
             // MCP server mode
-            let server = EvMcp::new().serve(stdio()).await?;
-            server.waiting().await?;
+            // Race the whole session, handshake included, against Ctrl-C
+            let session = async {
+                let reason = EvMcp::new().serve(stdio()).await?.waiting().await?;
+                tracing::debug!(?reason, "MCP server stopped");
+                anyhow::Ok(())
+            };
+            tokio::select! {
+                r = session => r?,
+                why = shutdown_signal() => {
+                    eprintln!("everything-mcp-rs: {}, shutting down", why);
+                    // Exit here: the stdin reader is a blocking read that would hold up runtime shutdown
+                    std::process::exit(0);
+                }
+            }
         }
     }
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use api::{MockApi, MockResult};
+
+    /// 2024-01-02 03:04:00 UTC
+    const FT: u64 = 133_486_382_400_000_000;
+
+    fn read(api: &MockApi, q: &str, o: &Opts) -> (u32, Vec<Hit>) {
+        run_search(api, q, o, o.max, request_flags(o)).unwrap();
+        read_hits(api, o)
+    }
+
+    // Off Windows there is no default DLL to fall back to, so a bad path always fails
+    #[cfg(not(windows))]
+    #[test]
+    fn bad_dll_path_is_named_in_the_error() {
+        let e = EvDll::load_chosen(None, Some("/nowhere/Everything64.dll".into())).err().unwrap();
+        assert!(e.starts_with("EVERYTHING_DLL_PATH=/nowhere/Everything64.dll: "), "{}", e);
+        let e = EvDll::load_chosen(Some("/flag/Everything64.dll".into()), Some("/env/Everything64.dll".into())).err().unwrap();
+        assert!(e.starts_with("--dll-path=/flag/Everything64.dll: ") && !e.contains("/env/"), "{}", e);
+        // An empty variable counts as unset
+        assert!(!EvDll::load_chosen(None, Some(String::new())).err().unwrap().contains("EVERYTHING_DLL_PATH"));
+    }
+
+    #[test]
+    fn dll_path_flag_parses_before_or_after_the_subcommand() {
+        let dll = |args: &[&str]| Cli::try_parse_from(args).map(|c| c.dll_path);
+        assert_eq!(dll(&["everything", "--dll-path", r"D:\sdk\Everything64.dll", "status"]).unwrap().as_deref(), Some(r"D:\sdk\Everything64.dll"));
+        assert_eq!(dll(&["everything", "search", "x", "--dll-path=E.dll"]).unwrap().as_deref(), Some("E.dll"));
+        assert_eq!(dll(&["everything", "status"]).unwrap(), None);
+        assert!(dll(&["everything", "status", "--dll-path"]).is_err());
+    }
+
+    #[test]
+    fn filetimes_convert_from_the_1601_epoch() {
+        let text = filetime_to_rfc3339;
+        assert_eq!(text(116_444_736_000_000_000).as_deref(), Some("1970-01-01T00:00:00Z"));
+        assert_eq!(text(132_539_328_000_000_000).as_deref(), Some("2021-01-01T00:00:00Z"));
+        assert_eq!(text(FT).as_deref(), Some("2024-01-02T03:04:00Z"));
+        // Sub-second ticks are dropped
+        assert_eq!(text(116_444_736_000_000_001).as_deref(), Some("1970-01-01T00:00:00Z"));
+        assert_eq!(text(0), None);
+        assert_eq!(text(u64::MAX), None);
+        assert_eq!(short_time("2024-01-02T03:04:00Z"), "2024-01-02 03:04");
+    }
+
+    #[test]
+    fn search_settings_reach_the_dll() {
+        let api = MockApi::new(&[]);
+        let o = Opts { max: 20, case: true, regex: true, sort: sort_code("size", false).unwrap(), offset: 40, timeout_ms: 500, ..Default::default() };
+        run_search(&api, "ext:rs", &o, o.max, request_flags(&o)).unwrap();
+        let s = api.state.borrow();
+        assert_eq!((s.search.as_str(), s.max, s.case, s.word, s.regex, s.path), ("ext:rs", 20, true, false, true, false));
+        assert_eq!((s.sort, s.offset, s.timeout_ms), (6, 40, 500));
+    }
+
+    #[test]
+    fn sort_zero_is_sent_as_name_ascending() {
+        let api = MockApi::new(&[]);
+        run_search(&api, "", &Opts::default(), 0, 0).unwrap();
+        assert_eq!(api.state.borrow().sort, 1);
+    }
+
+    #[test]
+    fn sort_codes_pair_ascending_and_descending() {
+        assert_eq!(sort_code("name", true), Ok(1));
+        assert_eq!(sort_code("date_modified", false), Ok(14));
+        assert_eq!(sort_code("date_accessed", true), Ok(23));
+        assert!(sort_code("owner", true).is_err());
+    }
+
+    #[test]
+    fn request_flags_follow_opts() {
+        let base = request_flags(&Opts::default());
+        assert_eq!(base, 0x1 | 0x2 | 0x8 | 0x10 | 0x40 | 0x100 | 0x400);
+        let all = request_flags(&Opts { highlight: true, created: true, accessed: true, file_list: true, ..Default::default() });
+        assert_eq!(all, base | REQUEST_HIGHLIGHTED_FILE_NAME | REQUEST_DATE_CREATED | REQUEST_DATE_ACCESSED | REQUEST_FILE_LIST_FILE_NAME);
+    }
+
+    #[test]
+    fn interior_nul_is_a_bad_query() {
+        let api = MockApi::new(&[]);
+        assert!(matches!(run_search(&api, "a\0b", &Opts::default(), 1, 0), Err(SearchError::BadQuery(_))));
+    }
+
+    #[test]
+    fn query_failure_is_passed_on() {
+        let api = MockApi { fail: Some(EverythingError::Ipc), ..Default::default() };
+        assert!(matches!(run_search(&api, "x", &Opts::default(), 1, 0), Err(SearchError::QueryFailed(EverythingError::Ipc))));
+    }
+
+    #[test]
+    fn columns_are_read_per_result() {
+        let api = MockApi {
+            results: vec![
+                MockResult {
+                    path: r"C:\src\main.rs".into(), file_name: Some("main.rs".into()), extension: Some("rs".into()),
+                    size: Some(2048), modified: Some(FT), attributes: 0x21, run_count: 3, ..Default::default()
+                },
+                MockResult { path: r"C:\src".into(), file_name: Some("src".into()), extension: Some(String::new()), size: Some(-1), attributes: 0x10, ..Default::default() },
+            ],
+            ..Default::default()
+        };
+        let (total, hits) = read(&api, "", &Opts::max(10));
+        assert_eq!(total, 2);
+        let (f, d) = (&hits[0], &hits[1]);
+        assert_eq!((f.path.as_str(), f.name.as_str(), f.extension.as_deref()), (r"C:\src\main.rs", "main.rs", Some("rs")));
+        assert_eq!((f.size, f.is_dir, f.attributes, f.run_count), (Some(2048), false, 0x21, 3));
+        assert_eq!(f.date_modified.as_deref(), Some("2024-01-02T03:04:00Z"));
+        // An empty extension is none, and a negative size unknown
+        assert_eq!((d.extension.as_deref(), d.size, d.is_dir, d.date_modified.as_deref()), (None, None, true, None));
+    }
+
+    #[test]
+    fn name_falls_back_to_the_last_path_component() {
+        let api = MockApi::new(&[r"D:\data\report.txt"]);
+        let (_, hits) = read(&api, "", &Opts::max(1));
+        assert_eq!(hits[0].name, "report.txt");
+    }
+
+    #[test]
+    fn optional_columns_only_when_asked_for() {
+        let r = MockResult {
+            path: r"C:\a\notes.md".into(), file_name: Some("notes.md".into()), created: Some(FT), accessed: Some(FT),
+            highlighted: Some("*not*es.md".into()), file_list: Some(r"C:\lists\a.efu".into()), ..Default::default()
+        };
+        let api = MockApi { results: vec![r], ..Default::default() };
+        let (_, plain) = read(&api, "", &Opts::max(1));
+        let h = &plain[0];
+        assert!(h.date_created.is_none() && h.date_accessed.is_none() && h.highlighted.is_none() && h.file_list.is_none());
+
+        let o = Opts { max: 1, created: true, accessed: true, highlight: true, file_list: true, ..Default::default() };
+        let (_, full) = read(&api, "", &o);
+        let h = &full[0];
+        assert_eq!((h.date_created.clone(), h.date_accessed.clone()), (filetime_to_rfc3339(FT), filetime_to_rfc3339(FT)));
+        assert_eq!((h.highlighted.as_deref(), h.matches.clone()), (Some("*not*es.md"), Some(vec![[0, 3]])));
+        assert_eq!(h.file_list.as_deref(), Some(r"C:\lists\a.efu"));
+    }
+
+    #[test]
+    fn empty_highlight_keeps_the_plain_name() {
+        let r = MockResult { path: r"C:\x".into(), highlighted: Some(String::new()), ..Default::default() };
+        let api = MockApi { results: vec![r], ..Default::default() };
+        let (_, hits) = read(&api, "", &Opts { max: 1, highlight: true, ..Default::default() });
+        assert!(hits[0].highlighted.is_none() && hits[0].matches.is_none());
+    }
+
+    #[test]
+    fn offset_pages_keep_absolute_indexes() {
+        let api = MockApi::new(&[r"C:\0", r"C:\1", r"C:\2", r"C:\3", r"C:\4"]);
+        let (total, hits) = read(&api, "", &Opts { max: 2, offset: 3, ..Default::default() });
+        assert_eq!(total, 5);
+        assert_eq!(hits.iter().map(|h| (h.index, h.path.as_str())).collect::<Vec<_>>(), [(3, r"C:\3"), (4, r"C:\4")]);
+    }
+
+    #[test]
+    fn long_paths_grow_the_buffer() {
+        let long = format!(r"C:\{}\f.txt", "d".repeat(*PATH_BUF_START * 2));
+        let api = MockApi::new(&[&long, r"C:\short"]);
+        let (_, hits) = read(&api, "", &Opts::max(2));
+        assert_eq!(hits[0].path, long);
+        assert_eq!(hits[1].path, r"C:\short");
+        let calls = api.path_calls.borrow();
+        // Full buffer, length query, then the retry in a buffer that fits; the next path needs no retry
+        assert_eq!(calls[..3], [*PATH_BUF_START, 0, long.len() + 1]);
+        assert_eq!(calls[3], long.len() + 1);
+    }
+
+    #[test]
+    fn paths_past_the_long_path_limit_are_read_whole() {
+        let long = format!(r"\\?\C:\{}", r"segment\".repeat(5000));
+        assert!(long.len() > MAX_PATH_BUF);
+        let api = MockApi::new(&[&long]);
+        api.state.borrow_mut().max = 1;
+        assert_eq!(result_path(&api, 0), long);
+        // The grown buffer is kept for the next search on this thread: the path fits it exactly, so only its
+        // length is checked
+        api.path_calls.borrow_mut().clear();
+        assert_eq!(result_path(&api, 0), long);
+        assert_eq!(*api.path_calls.borrow(), [long.len() + 1, 0]);
+    }
+
+    #[test]
+    fn a_path_that_exactly_fills_the_buffer_is_not_truncated() {
+        let fits = format!(r"C:\{}", "f".repeat(*PATH_BUF_START - 4));
+        assert_eq!(fits.len() + 1, *PATH_BUF_START);
+        let api = MockApi::new(&[&fits]);
+        api.state.borrow_mut().max = 1;
+        assert_eq!(result_path(&api, 0), fits);
+        // A full buffer could mean a cut-off path, so the length is checked, but nothing is re-read
+        assert_eq!(*api.path_calls.borrow(), [*PATH_BUF_START, 0]);
+    }
+
+    #[test]
+    fn default_layout_lists_one_line_per_hit() {
+        let api = MockApi {
+            results: vec![
+                MockResult { path: r"C:\docs\a.txt".into(), file_name: Some("a.txt".into()), size: Some(723), modified: Some(FT), attributes: 0x1, ..Default::default() },
+                MockResult { path: r"C:\docs".into(), file_name: Some("docs".into()), attributes: 0x10, ..Default::default() },
+            ],
+            total: Some(12),
+            ..Default::default()
+        };
+        let (total, hits) = read(&api, "", &Opts::max(2));
+        let text = format_hits_as("docs", total, 4, &hits, None, true);
+        assert_eq!(text, "Found 12 (showing 5-6 of 12):\n\n\
+            [FILE]     723 B R     C:\\docs\\a.txt (modified 2024-01-02 03:04)\n\
+            [DIR]          - D     C:\\docs\n");
+        assert_eq!(format_hits_as("docs", 12, 4, &hits, None, false).lines().count(), 2);
+    }
+
+    #[test]
+    fn highlighted_name_replaces_the_plain_one() {
+        let r = MockResult { path: r"C:\a\notes.md".into(), file_name: Some("notes.md".into()), highlighted: Some("*not*es.md".into()), ..Default::default() };
+        let api = MockApi { results: vec![r], ..Default::default() };
+        let (total, hits) = read(&api, "", &Opts { max: 1, highlight: true, ..Default::default() });
+        assert!(format_hits_as("not", total, 0, &hits, None, false).ends_with("C:\\a\\*not*es.md\n"));
+    }
+
+    #[test]
+    fn empty_pages_explain_themselves() {
+        assert_eq!(format_hits_as("zzz", 0, 0, &[], None, true), "No results for: zzz");
+        assert_eq!(format_hits_as("a", 3, 10, &[], None, true), "Found 3 (showing none: offset 10 is past the end)");
+        assert_eq!(format_hits_as("a", 3, 10, &[], None, false), "");
+    }
+
+    #[test]
+    fn attribute_bits_read_as_letters() {
+        assert_eq!(attr_flags_to_string(0x23), "RHA");
+        assert_eq!(attr_flags_to_string(0x10), "D");
+        assert_eq!(attr_flags_to_string(0), "");
+        assert_eq!(attr_flags_to_string(0x7f37), "RHSDATPLCOIE");
+        // Bits without a letter are dropped
+        assert_eq!(attr_flags_to_string(0x8 | 0x80 | 0x1_0000), "");
+    }
+
+    #[test]
+    fn attribute_names_parse_to_letters() {
+        assert_eq!(parse_attr_names("hidden, system").unwrap(), "HS");
+        assert_eq!(parse_attr_names(" ReadOnly ,,archive,").unwrap(), "RA");
+        assert_eq!(parse_attr_names("").unwrap(), "");
+        let e = parse_attr_names("hidden,secret").unwrap_err();
+        assert!(e.starts_with("Unknown attribute 'secret' (expected readonly, hidden, system,"), "{}", e);
+    }
+
+    #[test]
+    fn json_attributes_are_a_map_of_every_flag() {
+        let api = MockApi { results: vec![MockResult { path: r"C:\x".into(), attributes: 0x21, ..Default::default() }], ..Default::default() };
+        let (_, hits) = read(&api, "", &Opts::max(1));
+        let attrs = serde_json::to_value(&hits[0]).unwrap()["attributes"].clone();
+        let attrs = attrs.as_object().unwrap();
+        assert_eq!(attrs.len(), ATTRIBUTES.len());
+        let mut set: Vec<&str> = attrs.iter().filter(|(_, v)| v.as_bool() == Some(true)).map(|(k, _)| k.as_str()).collect();
+        set.sort_unstable();
+        assert_eq!(set, ["archive", "readonly"]);
+    }
+
+    #[test]
+    fn json_pages_report_total_shown_and_offset() {
+        let api = MockApi { total: Some(40), ..MockApi::new(&[r"C:\0", r"C:\1", r"C:\2", r"C:\3"]) };
+        let (total, hits) = read(&api, "", &Opts { max: 2, offset: 2, ..Default::default() });
+        let v = search_json(total, 2, &hits);
+        assert_eq!((v["total"].as_u64(), v["shown"].as_u64(), v["offset"].as_u64()), (Some(40), Some(2), Some(2)));
+        let results = v["results"].as_array().unwrap();
+        assert_eq!(results.iter().map(|r| r["index"].as_u64().unwrap()).collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(results[1]["path"], r"C:\3");
+
+        // The query_id resolves the page's absolute indexes, and only those
+        let id = v["query_id"].as_str().unwrap().to_string();
+        let at = |index| resolve_path(&PathReq { path: None, query_id: Some(id.clone()), index: Some(index) });
+        assert_eq!(at(3).unwrap(), r"C:\3");
+        assert!(at(1).unwrap_err().message.contains("has 2..4"));
+        assert_ne!(search_json(total, 2, &hits)["query_id"], v["query_id"]);
+    }
+
+    #[test]
+    fn ndjson_is_one_object_per_line_then_a_summary() {
+        let api = MockApi { total: Some(9), ..MockApi::new(&[r"C:\a,b.txt", "C:\\new\nline"]) };
+        let (total, hits) = read(&api, "", &Opts::max(2));
+        let out = ndjson(json_page(total, 0, &hits));
+        assert!(out.ends_with('\n'));
+        let lines: Vec<serde_json::Value> = out.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!((lines[0]["path"].as_str(), lines[1]["path"].as_str()), (Some(r"C:\a,b.txt"), Some("C:\\new\nline")));
+        assert_eq!(lines[2], serde_json::json!({ "type": "summary", "total": 9, "shown": 2, "offset": 0 }));
+        // An empty page is just the summary
+        assert_eq!(ndjson(json_page(0, 0, &[])).lines().count(), 1);
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_per_rfc_4180() {
+        assert_eq!(csv_field(r"C:\plain.txt"), r"C:\plain.txt");
+        assert_eq!(csv_field("a,b"), r#""a,b""#);
+        assert_eq!(csv_field(r#"say "hi""#), r#""say ""hi""""#);
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("cr\r"), "\"cr\r\"");
+        assert_eq!(csv_field(""), "");
+    }
+
+    #[test]
+    fn csv_has_a_header_and_one_row_per_hit() {
+        let api = MockApi {
+            results: vec![
+                MockResult { path: r"C:\a,b\x.txt".into(), file_name: Some("x.txt".into()), extension: Some("txt".into()), size: Some(5), modified: Some(FT), attributes: 0x21, ..Default::default() },
+                MockResult { path: r"C:\a,b".into(), file_name: Some("a,b".into()), attributes: 0x10, ..Default::default() },
+            ],
+            ..Default::default()
+        };
+        let (_, hits) = read(&api, "", &Opts::max(2));
+        assert_eq!(format_csv(&hits), "path,name,extension,is_dir,size,date_modified,date_created,attributes\n\
+            \"C:\\a,b\\x.txt\",x.txt,txt,false,5,2024-01-02T03:04:00Z,,RA\n\
+            \"C:\\a,b\",\"a,b\",,true,,,,D\n");
+    }
 
+    #[test]
+    fn sizes_format_at_unit_boundaries() {
+        for (n, want) in [(0, "0 B"), (1023, "1023 B"), (1024, "1 KB"), (1536, "2 KB"), (740_352, "723 KB"),
+            (1_048_575, "1.0 MB"), (1_048_576, "1.0 MB"), (1_610_612_736, "1.5 GB"), (1 << 40, "1.0 TB"), (u64::MAX, "16777216.0 TB")] {
+            assert_eq!(format_size_as(n, false), want, "{}", n);
+        }
+    }
+
+    #[test]
+    fn si_sizes_step_by_1000() {
+        for (n, want) in [(999, "999 B"), (1000, "1 KB"), (1023, "1 KB"), (999_999, "1.0 MB"), (1_500_000_000, "1.5 GB")] {
+            assert_eq!(format_size_as(n, true), want, "{}", n);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn dedup_drops_other_spellings_of_a_file_and_renumbers() {
+        let dir = std::env::temp_dir().join(format!("dedup-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink(dir.join("a.txt"), dir.join("link.txt")).unwrap();
+        let p = |s: &str| dir.join(s).to_string_lossy().into_owned();
+        let (a, dotted, link, b) = (p("a.txt"), p("sub/../a.txt"), p("link.txt"), p("missing.txt"));
+        let api = MockApi::new(&[&a, &dotted, &b, &link]);
+        let (_, mut hits) = read(&api, "", &Opts::max(4));
+        let removed = dedup_hits(&mut hits, 10).await;
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(removed, 2);
+        assert_eq!(hits.iter().map(|h| (h.index, h.path.as_str())).collect::<Vec<_>>(), [(10, a.as_str()), (11, b.as_str())]);
+    }
+}
@@ -0,0 +1,85 @@
+//! Mojibake heuristics for everything_find_mojibake
+//!
+//! Targets the common case of UTF-8 bytes decoded as Windows-1252/Latin-1 ("cafÃ©" for "café").
+//! Names in other legacy code pages (Shift-JIS, KOI8-R, ...) mangled the same way are not detected,
+//! and a genuine name that happens to contain "Ã©" will be flagged.
+
+/// Windows-1252 characters for bytes 0x80..=0x9F (None = undefined in 1252, kept as the C1 control)
+const CP1252_HIGH: [Option<char>; 32] = [
+    Some('€'), None, Some('‚'), Some('ƒ'), Some('„'), Some('…'), Some('†'), Some('‡'),
+    Some('ˆ'), Some('‰'), Some('Š'), Some('‹'), Some('Œ'), None, Some('Ž'), None,
+    None, Some('‘'), Some('’'), Some('“'), Some('”'), Some('•'), Some('–'), Some('—'),
+    Some('˜'), Some('™'), Some('š'), Some('›'), Some('œ'), None, Some('ž'), Some('Ÿ'),
+];
+
+/// Byte a character came from if the text was decoded as Windows-1252
+fn cp1252_byte(c: char) -> Option<u8> {
+    if (c as u32) < 0x100 { return Some(c as u8); }
+    CP1252_HIGH.iter().position(|&m| m == Some(c)).map(|i| 0x80 + i as u8)
+}
+
+/// Re-encode as Windows-1252 and decode as UTF-8; Some if that round trip yields different, valid text
+pub fn repair(name: &str) -> Option<String> {
+    let bytes: Option<Vec<u8>> = name.chars().map(cp1252_byte).collect();
+    let fixed = String::from_utf8(bytes?).ok()?;
+    (fixed != name).then_some(fixed)
+}
+
+/// Confidence (0.0-1.0) that a file name is mojibake, with the likely original or a reason
+pub fn score(name: &str) -> Option<(f32, String)> {
+    if name.is_ascii() { return None; }
+    if let Some(fixed) = repair(name) {
+        // Several repaired characters is much stronger evidence than a single lucky pair
+        let fixes = name.chars().count() - fixed.chars().count();
+        return Some((if fixes >= 2 { 0.95 } else { 0.8 }, format!("likely \"{}\"", fixed)));
+    }
+    if name.contains('\u{FFFD}') { return Some((0.7, "contains U+FFFD replacement character".into())); }
+    let marks = ["Ã", "Â", "â€", "Ð", "Ñ"].iter().map(|m| name.matches(m).count()).sum::<usize>();
+    if marks > 0 {
+        // Markers found but the round trip failed: partially mangled or double-encoded more than once
+        return Some(((0.3 + 0.1 * marks as f32).min(0.6), format!("{} double-encoding marker(s)", marks)));
+    }
+    if name.chars().any(|c| ('\u{80}'..='\u{9F}').contains(&c)) { return Some((0.5, "contains C1 control characters".into())); }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repair_undoes_utf8_read_as_1252() {
+        assert_eq!(repair("cafÃ©.txt").as_deref(), Some("café.txt"));
+        assert_eq!(repair("donâ€™t.txt").as_deref(), Some("don’t.txt"));
+        assert_eq!(repair("ÐŸÑ€Ð¸Ð²ÐµÑ‚.md").as_deref(), Some("Привет.md"));
+    }
+
+    #[test]
+    fn repair_leaves_clean_names_alone() {
+        for name in ["report.pdf", "café.txt", "résumé.doc", "日本語.txt", "naïve – notes.md"] {
+            assert_eq!(repair(name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn score_rates_repairable_names_highest() {
+        let (s, why) = score("cafÃ©.txt").unwrap();
+        assert_eq!((s, why.as_str()), (0.8, "likely \"café.txt\""));
+        assert_eq!(score("naÃ¯ve rÃ©sumÃ©.doc").unwrap().0, 0.95);
+    }
+
+    #[test]
+    fn score_falls_back_to_weaker_signs() {
+        assert_eq!(score("bad\u{FFFD}name.txt").unwrap().0, 0.7);
+        // A marker that won't round-trip because the rest of the name isn't 1252
+        assert_eq!(score("Ã日本.txt"), Some((0.4, "1 double-encoding marker(s)".into())));
+        assert_eq!(score("a\u{85}b.txt").unwrap().0, 0.5);
+    }
+
+    #[test]
+    fn clean_names_score_nothing() {
+        for name in ["report.pdf", "café.txt", "日本語.txt", "Ελληνικά.doc"] {
+            assert_eq!(score(name), None, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,19 @@
+//! Reading a result's full path into a reused UTF-16 buffer. Nothing here touches the DLL, so
+//! benches/path_buf.rs can include this file and measure it on its own
+
+/// Copy a path into `buf` with `get`, which follows Everything_GetResultFullPathNameW: it copies at most
+/// `len - 1` units plus a nul and returns the length copied, and an empty slice returns the length needed.
+/// `buf` only grows, at least doubling, when a path doesn't fit. Returns the units copied and the units the
+/// path needs, which differ only if the path changed between calls
+pub fn read_into(buf: &mut Vec<u16>, mut get: impl FnMut(&mut [u16]) -> u32) -> (usize, usize) {
+    let mut n = get(buf) as usize;
+    // A full buffer may hold a cut-off path
+    if n + 1 < buf.len() { return (n, n); }
+    let need = get(&mut []) as usize + 1;
+    if need > buf.len() {
+        let size = need.max(buf.len() * 2);
+        buf.resize(size, 0);
+        n = get(buf) as usize;
+    }
+    (n.min(buf.len()), need - 1)
+}
@@ -0,0 +1,68 @@
+//! Bounded-parallel per-result enrichment (stat, read, hash...) for tools that touch files
+
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// Blocking tasks allowed at once; override with EVERYTHING_WORKERS
+pub static WORKERS: Lazy<usize> = Lazy::new(|| {
+    std::env::var("EVERYTHING_WORKERS").ok().and_then(|v| v.parse().ok())
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(4, |n| n.get()))
+        .clamp(1, 64)
+});
+
+/// Apply blocking `f` to each item on tokio's blocking pool, at most `workers` at a time.
+/// Output order matches input order.
+pub async fn enrich<T, R, F>(items: Vec<T>, workers: usize, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    let (sem, f) = (Arc::new(Semaphore::new(workers.max(1))), Arc::new(f));
+    let mut tasks = Vec::with_capacity(items.len());
+    for item in items {
+        // Acquire before spawning so at most `workers` tasks exist at once (backpressure on the producer)
+        let permit = sem.clone().acquire_owned().await.expect("semaphore never closed");
+        let f = f.clone();
+        tasks.push(tokio::task::spawn_blocking(move || { let r = f(item); drop(permit); r }));
+    }
+    let mut out = Vec::with_capacity(tasks.len());
+    for t in tasks { out.push(t.await.expect("enrichment task panicked")); }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn output_keeps_input_order() {
+        // Later items finish first
+        let out = enrich((0..16u64).collect(), 8, |n| { std::thread::sleep(Duration::from_millis(16 - n)); n * 10 }).await;
+        assert_eq!(out, (0..16u64).map(|n| n * 10).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn never_more_than_workers_at_once() {
+        let (active, peak) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let (a, p) = (active.clone(), peak.clone());
+        enrich((0..24).collect(), 3, move |_: i32| {
+            let now = a.fetch_add(1, Ordering::SeqCst) + 1;
+            p.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(5));
+            a.fetch_sub(1, Ordering::SeqCst);
+        }).await;
+        let peak = peak.load(Ordering::SeqCst);
+        assert!((1..=3).contains(&peak), "peak {}", peak);
+    }
+
+    #[tokio::test]
+    async fn zero_workers_still_runs_one_at_a_time() {
+        assert_eq!(enrich(vec!["a", "bb"], 0, str::len).await, [1, 2]);
+        assert!(enrich(Vec::<u8>::new(), 4, |b| b).await.is_empty());
+    }
+}
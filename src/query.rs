@@ -0,0 +1,238 @@
+//! QueryBuilder: typed pieces of Everything search syntax, quoted and validated in one place
+
+use crate::filters;
+
+/// Collects query parts joined by spaces (Everything's AND). The first invalid value is kept and returned by build()
+#[derive(Default)]
+pub struct QueryBuilder {
+    parts: Vec<String>,
+    err: Option<String>,
+}
+
+/// Quote a term when Everything would otherwise split or reinterpret it: whitespace, OR, groups, a leading NOT
+/// or a quote. Functions (`ext:txt`) and wildcards are left alone so callers can still pass them as terms
+fn term(t: &str) -> String {
+    let t = t.trim();
+    if t.contains(char::is_whitespace) || t.contains(['|', '<', '>', '"']) || t.starts_with('!') {
+        format!("\"{}\"", filters::quote_escape(t))
+    } else {
+        t.to_string()
+    }
+}
+
+/// `"C:\dir\"`: a quoted folder with a trailing separator, matching everything below it
+fn folder(path: &str) -> Result<String, String> {
+    Ok(format!("\"{}\\\"", filters::quote_escape(&filters::normalize_folder(path)?)))
+}
+
+impl QueryBuilder {
+    pub fn new() -> Self { Self::default() }
+
+    fn push(mut self, part: Result<String, String>) -> Self {
+        match part {
+            Ok(p) if !p.is_empty() => self.parts.push(p),
+            Ok(_) => {}
+            Err(e) => { self.err.get_or_insert(e); }
+        }
+        self
+    }
+
+    /// Query text written by the user, appended as-is; blank is skipped
+    pub fn raw(self, q: &str) -> Self { let q = q.trim().to_string(); self.push(Ok(q)) }
+
+    /// raw() for optional keywords
+    pub fn keywords(self, q: Option<&str>) -> Self { self.raw(q.unwrap_or("")) }
+
+    /// `name:value`, quoting a value with whitespace, `|` or quotes; `<`, `>` and `=` stay bare as comparisons
+    /// (`runcount:>=3`). An empty value gives the bare modifier (`file:`)
+    pub fn func(self, name: &str, value: &str) -> Self {
+        let v = value.trim();
+        let part = if v.is_empty() {
+            format!("{}:", name)
+        } else if v.contains(char::is_whitespace) || v.contains(['|', '"']) {
+            format!("{}:\"{}\"", name, filters::quote_escape(v))
+        } else {
+            format!("{}:{}", name, v)
+        };
+        self.push(Ok(part))
+    }
+
+    pub fn files_only(self) -> Self { self.func("file", "") }
+
+    pub fn folders_only(self) -> Self { self.func("folder", "") }
+
+    /// `ext:a;b` from a comma- or semicolon-separated list; leading dots are dropped and a blank list is skipped
+    pub fn ext(self, list: &str) -> Self {
+        let exts: Vec<&str> = list.split([',', ';']).map(|e| e.trim().trim_start_matches('.')).filter(|e| !e.is_empty()).collect();
+        if exts.is_empty() { return self; }
+        if let Some(bad) = exts.iter().find(|e| e.contains(|c: char| c.is_whitespace() || "\"|<>*?".contains(c))) {
+            return self.push(Err(format!("Invalid extension '{}'", bad)));
+        }
+        self.push(Ok(format!("ext:{}", exts.join(";"))))
+    }
+
+    /// Everything below a folder
+    pub fn in_folder(self, path: &str) -> Self { self.push(folder(path)) }
+
+    /// Everything below any of the folders
+    pub fn in_any_folder(self, paths: &[&str]) -> Self {
+        let fs: Result<Vec<String>, String> = paths.iter().filter(|p| !p.trim().is_empty()).map(|p| folder(p)).collect();
+        match fs {
+            Ok(fs) if fs.is_empty() => self.push(Err("no folders given".into())),
+            Ok(fs) => self.push(Ok(group(fs))),
+            Err(e) => self.push(Err(e)),
+        }
+    }
+
+    /// `parent:"C:\dir"`: only the folder's direct children, not the whole subtree. A drive root keeps its
+    /// separator (`parent:"C:\"`)
+    pub fn parent(self, path: &str) -> Self {
+        let part = filters::normalize_folder(path).map(|p| {
+            let sep = if p.ends_with(':') { "\\" } else { "" };
+            format!("parent:\"{}{}\"", filters::quote_escape(&p), sep)
+        });
+        self.push(part)
+    }
+
+    /// `size:>N` from a human size like 500mb
+    pub fn size_gt(self, size: &str) -> Self { self.push(filters::parse_size(size).map(|s| format!("size:>{}", s))) }
+
+    /// `size:` with a full filter (`>1gb`, `1mb..2mb`, `huge`)
+    pub fn size(self, filter: &str) -> Self { self.push(filters::size_filter(filter).map(|f| format!("size:{}", f))) }
+
+    /// Inclusive size range with either end open
+    pub fn size_range(self, min: Option<&str>, max: Option<&str>) -> Self { self.push(filters::size_range(min, max)) }
+
+    /// `dm:` with a checked date filter
+    pub fn modified(self, date: &str) -> Self { self.date("dm", date) }
+
+    /// `dc:` with a checked date filter
+    pub fn created(self, date: &str) -> Self { self.date("dc", date) }
+
+    fn date(self, func: &str, date: &str) -> Self {
+        let d = date.trim();
+        let part = filters::check_date(d).map(|_| format!("{}:{}", func, d));
+        self.push(part)
+    }
+
+    /// `!term`, or `!"dir\"` for something that looks like a path, which drops the whole subtree
+    pub fn exclude(self, t: &str) -> Self {
+        let t = t.trim();
+        if t.is_empty() { return self; }
+        let part = if t.contains(['\\', '/']) { folder(t) } else { Ok(term(t)) };
+        self.push(part.map(|p| format!("!{}", p)))
+    }
+
+    /// `<a | b | c>`: at least one must match. Blank terms are skipped; one term needs no group
+    pub fn or_terms(self, terms: &[&str]) -> Self {
+        let ts: Vec<String> = terms.iter().filter(|t| !t.trim().is_empty()).map(|t| term(t)).collect();
+        if ts.is_empty() { return self; }
+        self.push(Ok(group(ts)))
+    }
+
+    /// `content:"text"`; Everything reads matching files, so this is slow
+    pub fn content(self, text: &str) -> Self {
+        if text.is_empty() { return self.push(Err("content is empty".into())); }
+        self.push(Ok(format!("content:\"{}\"", filters::quote_escape(text))))
+    }
+
+    /// The query, or the first invalid value
+    pub fn build(self) -> Result<String, String> {
+        match self.err {
+            Some(e) => Err(e),
+            None => Ok(self.parts.join(" ")),
+        }
+    }
+}
+
+fn group(parts: Vec<String>) -> String {
+    if parts.len() == 1 { parts.into_iter().next().unwrap_or_default() } else { format!("<{}>", parts.join(" | ")) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn q() -> QueryBuilder { QueryBuilder::new() }
+
+    #[test]
+    fn parts_are_joined_with_spaces() {
+        assert_eq!(q().files_only().raw(" report ").keywords(None).build().unwrap(), "file: report");
+        assert_eq!(q().raw("").keywords(Some("  ")).build().unwrap(), "");
+    }
+
+    #[test]
+    fn ext_list_is_one_semicolon_function() {
+        assert_eq!(q().ext(".rs, toml;md").build().unwrap(), "ext:rs;toml;md");
+        assert_eq!(q().ext(" , ").raw("x").build().unwrap(), "x");
+        assert!(q().ext("r s").build().is_err());
+        assert!(q().ext("*.rs").build().is_err());
+    }
+
+    #[test]
+    fn ext_with_keywords_needs_no_parentheses() {
+        assert_eq!(q().ext("jpg,png").keywords(Some("holiday")).build().unwrap(), "ext:jpg;png holiday");
+    }
+
+    #[test]
+    fn func_quotes_only_when_needed() {
+        assert_eq!(q().func("dupe", "").build().unwrap(), "dupe:");
+        assert_eq!(q().func("runcount", ">=3").build().unwrap(), "runcount:>=3");
+        assert_eq!(q().func("filelist", "my list.efu").build().unwrap(), r#"filelist:"my list.efu""#);
+        assert_eq!(q().func("wfn", r#"a"b"#).build().unwrap(), r#"wfn:"a""b""#);
+    }
+
+    #[test]
+    fn folders_are_quoted_with_a_trailing_separator() {
+        assert_eq!(q().in_folder(r" C:\Projects\ ").build().unwrap(), r#""C:\Projects\""#);
+        assert_eq!(q().in_folder(r#""C:\My Docs""#).build().unwrap(), r#""C:\My Docs\""#);
+        assert_eq!(q().in_any_folder(&[r"C:\a", "", r"D:\b"]).build().unwrap(), r#"<"C:\a\" | "D:\b\">"#);
+        assert!(q().in_folder("  ").build().is_err());
+        assert!(q().in_any_folder(&[""]).build().is_err());
+    }
+
+    #[test]
+    fn parent_keeps_a_drive_roots_separator() {
+        assert_eq!(q().parent(r"C:\src\").build().unwrap(), r#"parent:"C:\src""#);
+        assert_eq!(q().parent(r"C:\").build().unwrap(), r#"parent:"C:\""#);
+        assert_eq!(q().parent("D:").build().unwrap(), r#"parent:"D:\""#);
+    }
+
+    #[test]
+    fn quotes_in_values_are_doubled() {
+        assert_eq!(q().content(r#"say "hi""#).build().unwrap(), r#"content:"say ""hi""""#);
+        assert_eq!(q().in_folder(r#"C:\a"b"#).build().unwrap(), r#""C:\a""b\""#);
+        assert_eq!(q().or_terms(&[r#"x"y"#]).build().unwrap(), r#""x""y""#);
+        // Backslashes are not escapes
+        assert_eq!(q().content(r"C:\temp\").build().unwrap(), r#"content:"C:\temp\""#);
+        assert!(q().content("").build().is_err());
+    }
+
+    #[test]
+    fn or_terms_group_and_quote() {
+        assert_eq!(q().or_terms(&["a", " ", "b c"]).build().unwrap(), r#"<a | "b c">"#);
+        assert_eq!(q().or_terms(&["only"]).build().unwrap(), "only");
+        assert_eq!(q().or_terms(&["!x", "a|b", "*.rs"]).build().unwrap(), r#"<"!x" | "a|b" | *.rs>"#);
+        assert_eq!(q().or_terms(&[]).raw("z").build().unwrap(), "z");
+    }
+
+    #[test]
+    fn exclude_drops_terms_or_whole_folders() {
+        assert_eq!(q().exclude("tmp").exclude(r"C:\build\").exclude("").build().unwrap(), r#"!tmp !"C:\build\""#);
+        assert_eq!(q().exclude("old stuff").build().unwrap(), r#"!"old stuff""#);
+    }
+
+    #[test]
+    fn dates_and_sizes_are_checked() {
+        assert_eq!(q().modified("last7days").created(">2024-01-01").build().unwrap(), "dm:last7days dc:>2024-01-01");
+        assert!(q().modified("yesterdayish").build().is_err());
+        assert_eq!(q().size_gt("1.5gb").build().unwrap(), "size:>1536mb");
+        assert!(q().size_gt("big").build().is_err());
+    }
+
+    #[test]
+    fn first_invalid_value_wins() {
+        let e = q().ext("a b").modified("nope").build().unwrap_err();
+        assert!(e.contains("Invalid extension 'a b'"), "{}", e);
+    }
+}
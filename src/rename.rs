@@ -0,0 +1,117 @@
+//! Bulk rename preview: computes new names without touching the disk
+
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Characters Windows rejects in a file name
+const BAD: &[char] = &['\\', '/', ':', '*', '?', '"', '<', '>', '|'];
+
+/// New file name for `name`, or None when the pattern doesn't match or leaves it unchanged
+pub fn rename(name: &str, re: &Regex, rep: &str) -> Option<String> {
+    let new = re.replace(name, rep);
+    (re.is_match(name) && new != name).then(|| new.into_owned())
+}
+
+/// old -> new pairs for every matching path, with collisions flagged.
+/// Targets compare case-insensitively, as on NTFS; an existing file only counts if it isn't itself being renamed.
+pub fn preview(paths: &[String], re: &Regex, rep: &str) -> String {
+    let mut plan = Vec::new();
+    for p in paths {
+        let path = Path::new(p);
+        let Some(name) = path.file_name().map(|n| n.to_string_lossy()) else { continue };
+        if let Some(new) = rename(&name, re, rep) { plan.push((p.as_str(), path.with_file_name(&new), new)); }
+    }
+    if plan.is_empty() { return "Pattern matched no names (nothing to rename)".into(); }
+
+    let key = |p: &Path| p.to_string_lossy().to_lowercase();
+    let sources: Vec<String> = plan.iter().map(|(old, _, _)| old.to_lowercase()).collect();
+    let mut targets: HashMap<String, usize> = HashMap::new();
+    for (_, to, _) in &plan { *targets.entry(key(to)).or_default() += 1; }
+
+    let mut collisions = 0;
+    let mut out = String::new();
+    for (old, to, new) in &plan {
+        let k = key(to);
+        let why = if new.is_empty() || new.contains(BAD) {
+            Some("invalid file name")
+        } else if targets[&k] > 1 {
+            Some("same target as another file")
+        } else if to.exists() && !sources.contains(&k) {
+            Some("target already exists")
+        } else { None };
+        match why {
+            Some(w) => { collisions += 1; out.push_str(&format!("[CONFLICT: {}] {} -> {}\n", w, old, new)); }
+            None => out.push_str(&format!("{} -> {}\n", old, new)),
+        }
+    }
+    format!("Preview only, nothing renamed. {} rename(s), {} conflict(s):\n\n{}", plan.len(), collisions, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn re(p: &str) -> Regex { Regex::new(p).unwrap() }
+
+    fn paths(ps: &[&str]) -> Vec<String> { ps.iter().map(|p| p.to_string()).collect() }
+
+    #[test]
+    fn rename_uses_capture_groups() {
+        assert_eq!(rename("IMG_1234.jpg", &re(r"^IMG_(\d+)"), "photo-$1").as_deref(), Some("photo-1234.jpg"));
+        assert_eq!(rename("notes.txt", &re(r"^(\w+)\.txt$"), "${1}.md").as_deref(), Some("notes.md"));
+    }
+
+    #[test]
+    fn no_match_or_no_change_is_none() {
+        assert_eq!(rename("a.txt", &re("zzz"), "b"), None);
+        assert_eq!(rename("a.txt", &re("a"), "a"), None);
+    }
+
+    #[test]
+    fn clean_plan_lists_every_rename() {
+        let out = preview(&paths(&["/d/a1.txt", "/d/a2.txt", "/d/b.txt"]), &re(r"^a(\d)"), "x$1");
+        assert_eq!(out, "Preview only, nothing renamed. 2 rename(s), 0 conflict(s):\n\n/d/a1.txt -> x1.txt\n/d/a2.txt -> x2.txt\n");
+    }
+
+    #[test]
+    fn shared_targets_collide_ignoring_case() {
+        let out = preview(&paths(&["/d/A-1.txt", "/d/a-2.txt"]), &re(r"-\d"), "");
+        assert!(out.contains("2 rename(s), 2 conflict(s)"), "{}", out);
+        assert!(out.contains("[CONFLICT: same target as another file] /d/A-1.txt -> A.txt"));
+        // Same name in different folders is fine
+        let out = preview(&paths(&["/d/a-1.txt", "/e/a-2.txt"]), &re(r"-\d"), "");
+        assert!(out.contains("0 conflict(s)"), "{}", out);
+    }
+
+    #[test]
+    fn invalid_names_are_conflicts() {
+        let out = preview(&paths(&["/d/a.txt", "/d/b.txt"]), &re(r"^a"), "x:y");
+        assert!(out.contains("[CONFLICT: invalid file name] /d/a.txt -> x:y.txt"), "{}", out);
+        let out = preview(&paths(&["/d/a.txt"]), &re(r".*"), "");
+        assert!(out.contains("[CONFLICT: invalid file name]"), "{}", out);
+    }
+
+    #[test]
+    fn existing_files_collide_unless_they_move_too() {
+        let dir = std::env::temp_dir().join(format!("everything-rename-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for f in ["a.txt", "b.txt"] { std::fs::write(dir.join(f), "").unwrap(); }
+        let p = |f: &str| dir.join(f).to_string_lossy().into_owned();
+
+        let out = preview(&[p("a.txt")], &re("^a"), "b");
+        assert!(out.contains("[CONFLICT: target already exists]"), "{}", out);
+        // b.txt is renamed away in the same plan, so a.txt may take its name
+        let out = preview(&[p("a.txt"), p("b.txt")], &re("^([ab])"), "${1}x");
+        assert!(out.contains("0 conflict(s)"), "{}", out);
+        // Listed but not matched, so it stays put
+        let out = preview(&[p("a.txt"), p("b.txt")], &re("^a"), "b");
+        assert!(out.contains("1 rename(s), 1 conflict(s)"), "{}", out);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn nothing_matched() {
+        assert_eq!(preview(&paths(&["/d/a.txt"]), &re("zzz"), "y"), "Pattern matched no names (nothing to rename)");
+    }
+}
@@ -0,0 +1,51 @@
+//! Named searches for everything_save_search / everything_run_saved: a JSON object mapping each name to the
+//! everything_search arguments it was saved with
+//!
+//! ```json
+//! { "big-logs": { "query": "ext:log size:>100mb", "sort_by": "size", "ascending": false } }
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+/// Serializes read-modify-write of the file between concurrent tool calls
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// `saved-searches.json` under %APPDATA% on Windows, else $XDG_CONFIG_HOME or ~/.config, in an everything-mcp-rs folder
+pub fn default_path() -> Option<PathBuf> {
+    let var = |k: &str| std::env::var_os(k).filter(|v| !v.is_empty()).map(PathBuf::from);
+    let base = if cfg!(windows) { var("APPDATA") } else { var("XDG_CONFIG_HOME").or_else(|| var("HOME").map(|h| h.join(".config"))) };
+    base.map(|b| b.join("everything-mcp-rs").join("saved-searches.json"))
+}
+
+/// Every saved search by name; a missing file is an empty list, a malformed one is an error
+pub fn load(path: &Path) -> Result<BTreeMap<String, Value>, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Save `entry` under `name`, dropping its null fields. An existing name is only replaced with `overwrite`;
+/// returns whether one was
+pub fn store(path: &Path, name: &str, mut entry: Value, overwrite: bool) -> Result<bool, String> {
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut all = load(path)?;
+    let replaced = all.contains_key(name);
+    if replaced && !overwrite {
+        return Err(format!("A saved search named '{}' already exists; pass overwrite: true to replace it", name));
+    }
+    if let Value::Object(m) = &mut entry { m.retain(|_, v| !v.is_null()); }
+    all.insert(name.to_string(), entry);
+    if let Some(dir) = path.parent() { std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?; }
+    let text = serde_json::to_string_pretty(&all).map_err(|e| e.to_string())?;
+    // Write beside the file and rename over it, so a crash can't leave it half-written
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, text + "\n").and_then(|_| std::fs::rename(&tmp, path)).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(replaced)
+}
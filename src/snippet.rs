@@ -0,0 +1,123 @@
+//! Matching-line previews for everything_content_preview, like grep -n with a little context
+
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Read;
+
+/// How much of each file to read and show
+#[derive(Clone, Copy)]
+pub struct Limits {
+    /// Matching lines per file
+    pub lines: usize,
+    /// Lines shown before and after each match
+    pub context: usize,
+    /// Characters per line; longer lines are clipped around the match
+    pub max_line_len: usize,
+    /// Bytes read per file; matches past this are not seen
+    pub max_bytes: u64,
+    pub case: bool,
+}
+
+pub enum Preview {
+    /// (1-based line number, is a match, text)
+    Lines(Vec<(usize, bool, String)>),
+    Binary,
+    NoMatch,
+}
+
+/// Read up to max_bytes of `path` and collect its first matching lines with context
+pub fn preview(path: &str, needle: &str, l: Limits) -> std::io::Result<Preview> {
+    let mut buf = Vec::new();
+    File::open(path)?.take(l.max_bytes).read_to_end(&mut buf)?;
+    // NUL bytes mean binary (or UTF-16, which Everything's content: also reads but a line view can't show)
+    if buf.contains(&0) { return Ok(Preview::Binary); }
+    let text = String::from_utf8_lossy(&buf);
+    let lines: Vec<&str> = text.lines().collect();
+    let fold = |s: &str| if l.case { s.to_string() } else { s.to_lowercase() };
+    let needle = fold(needle);
+    let hits: Vec<usize> = lines.iter().enumerate().filter(|(_, s)| fold(s).contains(&needle)).map(|(i, _)| i).take(l.lines.max(1)).collect();
+    if hits.is_empty() { return Ok(Preview::NoMatch); }
+    // Merge overlapping context windows so no line is printed twice
+    let mut shown: Vec<(usize, bool, String)> = Vec::new();
+    for &i in &hits {
+        let from = i.saturating_sub(l.context).max(shown.last().map_or(0, |s| s.0));
+        for (j, line) in lines.iter().enumerate().take((i + l.context + 1).min(lines.len())).skip(from) {
+            shown.push((j + 1, hits.contains(&j), clip(line, &needle, l)));
+        }
+    }
+    Ok(Preview::Lines(shown))
+}
+
+/// Trim a line to max_line_len characters, keeping the match in view
+fn clip(line: &str, needle: &str, l: Limits) -> String {
+    let line = line.trim_end();
+    let len = line.chars().count();
+    if len <= l.max_line_len { return line.to_string(); }
+    let hay = if l.case { line.to_string() } else { line.to_lowercase() };
+    // Lowercasing can change byte lengths, so locate the match by character index
+    let at = hay.find(needle).map_or(0, |b| hay[..b].chars().count());
+    let start = at.saturating_sub(l.max_line_len / 4).min(len - l.max_line_len);
+    let mut out = String::new();
+    if start > 0 { out.push('…'); }
+    out.extend(line.chars().skip(start).take(l.max_line_len));
+    if start + l.max_line_len < len { out.push('…'); }
+    out
+}
+
+/// One block per file: the path, then `N: match` and `N- context` lines
+pub fn format(path: &str, p: &std::io::Result<Preview>, out: &mut String) {
+    let _ = match p {
+        Ok(Preview::Lines(ls)) => {
+            let _ = writeln!(out, "{}", path);
+            for (n, hit, text) in ls { let _ = writeln!(out, "  {:>5}{} {}", n, if *hit { ':' } else { '-' }, text); }
+            Ok(())
+        }
+        Ok(Preview::Binary) => writeln!(out, "{}  (binary, not shown)", path),
+        Ok(Preview::NoMatch) => writeln!(out, "{}  (no match within the bytes read)", path),
+        Err(e) => writeln!(out, "{}  (could not read: {})", path, e),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const L: Limits = Limits { lines: 3, context: 1, max_line_len: 20, max_bytes: 1 << 20, case: true };
+
+    #[test]
+    fn short_lines_are_only_trimmed() {
+        assert_eq!(clip("short line  \r", "line", L), "short line");
+        assert_eq!(clip(&"é".repeat(20), "é", L), "é".repeat(20));
+    }
+
+    #[test]
+    fn long_lines_keep_the_match_in_view() {
+        let line = format!("{}needle{}", "α".repeat(30), "β".repeat(30));
+        assert_eq!(clip(&line, "needle", L), format!("…{}needle{}…", "α".repeat(5), "β".repeat(9)));
+    }
+
+    #[test]
+    fn matches_are_found_by_character_not_byte() {
+        // ẞ is 3 bytes and lowercases to the 2-byte ß
+        let line = format!("{}NEEDLE{}", "ẞ".repeat(30), "x".repeat(30));
+        let l = Limits { case: false, ..L };
+        assert_eq!(clip(&line, "needle", l), format!("…{}NEEDLE{}…", "ẞ".repeat(5), "x".repeat(9)));
+    }
+
+    #[test]
+    fn clipping_stops_at_the_line_ends() {
+        let line = format!("{}end", "ü".repeat(40));
+        assert_eq!(clip(&line, "end", L), format!("…{}end", "ü".repeat(17)));
+        assert_eq!(clip(&line, "missing", L), format!("{}…", "ü".repeat(20)));
+    }
+
+    #[test]
+    fn context_windows_merge() {
+        let path = std::env::temp_dir().join(format!("snippet-test-{}.txt", std::process::id()));
+        std::fs::write(&path, "one\nTwo hit\nthree\nfour hit\nfive\nsix\nseven\n").unwrap();
+        let Ok(Preview::Lines(ls)) = preview(path.to_str().unwrap(), "HIT", Limits { case: false, ..L }) else { panic!("no lines") };
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(ls.iter().map(|(n, hit, _)| (*n, *hit)).collect::<Vec<_>>(), [(1, false), (2, true), (3, false), (4, true), (5, false)]);
+        assert_eq!(ls[1].2, "Two hit");
+    }
+}
@@ -0,0 +1,155 @@
+//! Per-result line templates for text output: `{path}`, `{size}`, ... substituted for each hit
+
+use std::fmt::Write as _;
+
+use crate::{attr_flags_to_string, format_size, short_time, Hit};
+
+const FIELDS: &str = "path, name, dir, ext, size, bytes, modified, created, accessed, attr, kind, index";
+
+#[derive(Clone, Copy, Debug)]
+enum Field { Path, Name, Dir, Ext, Size, Bytes, Modified, Created, Accessed, Attr, Kind, Index }
+
+#[derive(Clone, Debug)]
+enum Piece { Lit(String), Field(Field) }
+
+/// A parsed template; `{{` and `}}` are literal braces
+#[derive(Clone, Debug)]
+pub struct Template(Vec<Piece>);
+
+impl Template {
+    /// Parse and validate a template, rejecting unknown placeholders and stray braces
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (mut pieces, mut lit) = (Vec::new(), String::new());
+        let mut cs = s.chars().peekable();
+        while let Some(c) = cs.next() {
+            match c {
+                '{' if cs.peek() == Some(&'{') => { cs.next(); lit.push('{'); }
+                '}' if cs.peek() == Some(&'}') => { cs.next(); lit.push('}'); }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match cs.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("Unclosed '{{{}' in template (write {{{{ for a literal brace)", name)),
+                        }
+                    }
+                    let field = match name.as_str() {
+                        "path" => Field::Path,
+                        "name" => Field::Name,
+                        "dir" => Field::Dir,
+                        "ext" => Field::Ext,
+                        "size" => Field::Size,
+                        "bytes" => Field::Bytes,
+                        "modified" => Field::Modified,
+                        "created" => Field::Created,
+                        "accessed" => Field::Accessed,
+                        "attr" => Field::Attr,
+                        "kind" => Field::Kind,
+                        "index" => Field::Index,
+                        _ => return Err(format!("Unknown placeholder '{{{}}}' in template. Available: {}", name, FIELDS)),
+                    };
+                    if !lit.is_empty() { pieces.push(Piece::Lit(std::mem::take(&mut lit))); }
+                    pieces.push(Piece::Field(field));
+                }
+                '}' => return Err("Unmatched '}' in template (write }} for a literal brace)".into()),
+                c => lit.push(c),
+            }
+        }
+        if !lit.is_empty() { pieces.push(Piece::Lit(lit)); }
+        Ok(Template(pieces))
+    }
+
+    /// Whether {created} and {accessed} appear, so the search can ask for those columns
+    pub fn dates(&self) -> (bool, bool) {
+        let has = |want: fn(&Field) -> bool| self.0.iter().any(|p| matches!(p, Piece::Field(f) if want(f)));
+        (has(|f| matches!(f, Field::Created)), has(|f| matches!(f, Field::Accessed)))
+    }
+
+    /// Append one hit's line (without a newline); unknown values render as `-`
+    pub fn render(&self, h: &Hit, out: &mut String) {
+        let time = |t: &Option<String>| t.as_deref().map_or_else(|| "-".into(), short_time);
+        for p in &self.0 {
+            match p {
+                Piece::Lit(s) => out.push_str(s),
+                Piece::Field(f) => {
+                    let _ = match f {
+                        Field::Path => write!(out, "{}", h.path),
+                        Field::Name => write!(out, "{}", h.highlighted.as_deref().unwrap_or(&h.name)),
+                        Field::Dir => write!(out, "{}", h.path.strip_suffix(h.name.as_str()).unwrap_or("").trim_end_matches(['\\', '/'])),
+                        Field::Ext => write!(out, "{}", h.extension.as_deref().unwrap_or("")),
+                        Field::Size => write!(out, "{}", h.size.map(format_size).unwrap_or_else(|| "-".into())),
+                        Field::Bytes => write!(out, "{}", h.size.map(|n| n.to_string()).unwrap_or_else(|| "-".into())),
+                        Field::Modified => write!(out, "{}", time(&h.date_modified)),
+                        Field::Created => write!(out, "{}", time(&h.date_created)),
+                        Field::Accessed => write!(out, "{}", time(&h.date_accessed)),
+                        Field::Attr => write!(out, "{}", if h.attributes == 0 { "-".into() } else { attr_flags_to_string(h.attributes) }),
+                        Field::Kind => write!(out, "{}", if h.is_dir { "DIR" } else { "FILE" }),
+                        Field::Index => write!(out, "{}", h.index),
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit() -> Hit {
+        Hit {
+            index: 7, path: r"C:\docs\a.txt".into(), name: "a.txt".into(), extension: Some("txt".into()), is_dir: false,
+            size: Some(723), date_modified: Some("2024-01-02T03:04:00Z".into()), run_count: 0, date_created: None,
+            date_accessed: None, attributes: 0x21, highlighted: None, matches: None, file_list: None, live: None,
+        }
+    }
+
+    fn render(t: &str, h: &Hit) -> String {
+        let mut out = String::new();
+        Template::parse(t).unwrap().render(h, &mut out);
+        out
+    }
+
+    #[test]
+    fn placeholders_are_substituted() {
+        let h = hit();
+        assert_eq!(render("{index}: {kind} {name} in {dir} ({ext}, {size} = {bytes} bytes, {attr}) {modified}", &h),
+            r"7: FILE a.txt in C:\docs (txt, 723 B = 723 bytes, RA) 2024-01-02 03:04");
+        assert_eq!(render("{path}", &h), r"C:\docs\a.txt");
+        assert_eq!(render("no fields", &h), "no fields");
+        assert_eq!(render("", &h), "");
+    }
+
+    #[test]
+    fn missing_values_render_as_a_dash() {
+        let h = Hit { extension: None, size: None, date_modified: None, attributes: 0, is_dir: true, ..hit() };
+        assert_eq!(render("{ext}|{size}|{bytes}|{modified}|{created}|{accessed}|{attr}|{kind}", &h), "|-|-|-|-|-|-|DIR");
+    }
+
+    #[test]
+    fn highlighted_name_wins() {
+        let h = Hit { highlighted: Some("*a*.txt".into()), ..hit() };
+        assert_eq!(render("{name}", &h), "*a*.txt");
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(render("{{{name}}} }}{{", &hit()), "{a.txt} }{");
+    }
+
+    #[test]
+    fn bad_templates_are_rejected() {
+        assert!(Template::parse("{nope}").unwrap_err().starts_with("Unknown placeholder '{nope}'"));
+        assert!(Template::parse("{Path}").unwrap_err().contains(FIELDS));
+        assert_eq!(Template::parse("{path").unwrap_err(), "Unclosed '{path' in template (write {{ for a literal brace)");
+        assert!(Template::parse("a } b").unwrap_err().starts_with("Unmatched '}'"));
+    }
+
+    #[test]
+    fn dates_report_the_extra_columns() {
+        assert_eq!(Template::parse("{path} {modified}").unwrap().dates(), (false, false));
+        assert_eq!(Template::parse("{created}").unwrap().dates(), (true, false));
+        assert_eq!(Template::parse("{accessed} {created}").unwrap().dates(), (true, true));
+    }
+}
@@ -0,0 +1,96 @@
+//! The thread that owns Everything64.dll. The DLL keeps one global search state and result list, so every call
+//! into it runs here as a job, one at a time in the order sent, and callers await the reply instead of blocking a
+//! tokio worker on a lock
+
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc;
+
+use once_cell::sync::Lazy;
+use tokio::sync::oneshot;
+
+use crate::{EvDll, SearchError};
+
+type Job = Box<dyn FnOnce(&mut Result<EvDll, String>) + Send>;
+
+/// Started on first use, which is also when the DLL is loaded
+static JOBS: Lazy<mpsc::Sender<Job>> = Lazy::new(|| {
+    let (tx, rx) = mpsc::channel::<Job>();
+    std::thread::Builder::new().name("everything-dll".into()).spawn(move || {
+        let mut dll = EvDll::load();
+        for job in rx {
+            // Every query sets its own search state, so a panic mid-query leaves nothing the next job depends on;
+            // the caller sees it as a dropped reply
+            if std::panic::catch_unwind(AssertUnwindSafe(|| job(&mut dll))).is_err() {
+                tracing::warn!("Everything DLL job panicked; continuing with the next");
+            }
+        }
+    }).expect("spawn the Everything DLL thread");
+    tx
+});
+
+/// Run `f` on the DLL thread and await its result. A job that dies before answering (a panic, which aborts the
+/// process in release builds) is an error here rather than a panic in the caller
+pub async fn call<T: Send + 'static>(f: impl FnOnce(&mut Result<EvDll, String>) -> T + Send + 'static) -> Result<T, SearchError> {
+    let (tx, rx) = oneshot::channel();
+    JOBS.send(Box::new(move |dll| { let _ = tx.send(f(dll)); }))
+        .map_err(|_| SearchError::Backend("the Everything DLL thread has stopped".into()))?;
+    rx.await.map_err(|_| SearchError::Backend("the Everything DLL call failed without a reply".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::*;
+
+    thread_local! {
+        /// Stands in for the DLL's one global search state
+        static STATE: Cell<u32> = const { Cell::new(0) };
+    }
+
+    // A single-threaded runtime: a call that blocked its thread would stall the ticker until every job was done
+    #[tokio::test]
+    async fn parallel_calls_keep_the_runtime_free_and_their_own_results() {
+        let ticks = Arc::new(AtomicU32::new(0));
+        let ticker = tokio::spawn({
+            let ticks = ticks.clone();
+            async move {
+                loop {
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                    ticks.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        });
+        let jobs: Vec<_> = (0..32u32).map(|n| tokio::spawn(call(move |_| {
+            // Set, "query", read back: another job in between would show up as a wrong result
+            STATE.set(n);
+            std::thread::sleep(Duration::from_millis(2));
+            STATE.get()
+        }))).collect();
+        for (n, job) in jobs.into_iter().enumerate() {
+            assert_eq!(job.await.unwrap().unwrap(), n as u32);
+        }
+        ticker.abort();
+        assert!(ticks.load(Ordering::Relaxed) >= 5, "runtime stalled: {} ticks", ticks.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn a_job_that_dies_is_an_error_and_the_thread_carries_on() {
+        let r = call(|_| -> u32 { panic!("boom") }).await;
+        assert!(matches!(r, Err(SearchError::Backend(ref m)) if m.contains("without a reply")), "{:?}", r.err());
+        assert_eq!(call(|_| 7).await.unwrap(), 7);
+    }
+
+    // Where a poisoned lock used to fail every later call, a panic now costs only its own job
+    #[tokio::test]
+    async fn the_loaded_dll_outlives_a_panicking_job() {
+        let before = call(|dll| dll.as_ref().err().cloned()).await.unwrap();
+        for _ in 0..3 {
+            assert!(call(|_| -> u32 { panic!("mid-query") }).await.is_err());
+        }
+        assert_eq!(call(|dll| dll.as_ref().err().cloned()).await.unwrap(), before);
+    }
+}